@@ -0,0 +1,31 @@
+use chess::Board;
+use kittycat::SearchLimits;
+use std::str::FromStr;
+
+fn main() {
+    let fen = std::env::args().nth(1).unwrap_or_else(|| {
+        String::from("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+    });
+
+    let board = Board::from_str(&fen).expect("invalid fen");
+
+    let limits = SearchLimits {
+        max_depth: Some(8),
+        ..Default::default()
+    };
+
+    let result = kittycat::think(board, limits);
+
+    match result.best_move {
+        Some(best_move) => println!("best move {best_move}"),
+        None => println!("no legal moves"),
+    }
+
+    let pv: Vec<String> = result.pv.iter().map(ToString::to_string).collect();
+
+    println!(
+        "depth {} score cp {} nodes {}",
+        result.depth, result.score, result.nodes
+    );
+    println!("pv {}", pv.join(" "));
+}
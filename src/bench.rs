@@ -0,0 +1,78 @@
+use crate::{
+    search::{Search, SearchLimits},
+    tt::{TranspositionTable, DEFAULT_HASH_SIZE},
+};
+use chess::Board;
+use std::{str::FromStr, time::Instant};
+
+const BENCH_DEPTH: u8 = 7;
+
+const POSITIONS: [&str; 20] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+    "r1bq1rk1/ppp1nppp/4n3/3p3Q/3P4/1BP1B3/PP1N2PP/R4RK1 w - - 1 16",
+    "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
+    "2rqkb1r/ppp2p2/2npb1p1/1N1Nn2p/2P1PP2/8/PP2B1PP/R1BQK2R b KQ - 0 11",
+    "r1bq1r1k/b1p1npp1/p2p3p/1p6/3PP3/1B2NN2/PP3PPP/R2Q1RK1 w - - 1 16",
+    "3r1rk1/p5pp/bpp1pp2/8/q1PP1P2/b3P3/P2NQRPP/1R2B1K1 b - - 6 22",
+    "r1q2rk1/2p1bppp/2Pp4/p6b/Q1PNp3/4B3/PP1R1PPP/2K4R w - - 2 18",
+    "4k2r/1pb2ppp/1p2p3/1R1p4/3P4/2r1PN2/P4PPP/1R4K1 b - - 3 22",
+    "3q2k1/pb3p1p/4pbp1/2r5/PpN2N2/1P2P2P/5PP1/Q2R2K1 b - - 4 26",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+    "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
+    "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 1",
+    "8/3k4/8/8/8/4B3/4KB2/2B5 w - - 0 1",
+];
+
+pub fn run() {
+    let start_time = Instant::now();
+
+    let total_nodes = total_nodes(BENCH_DEPTH);
+
+    let elapsed = start_time.elapsed();
+
+    let nps = (total_nodes as f64 / elapsed.as_secs_f64()) as u64;
+
+    println!("{} nodes {} nps", total_nodes, nps);
+}
+
+// every run starts from an empty table, so the total only changes when the search does
+fn total_nodes(depth: u8) -> u64 {
+    let tt = TranspositionTable::new(DEFAULT_HASH_SIZE);
+
+    let mut total_nodes = 0;
+
+    for fen in POSITIONS {
+        let board = Board::from_str(fen).unwrap();
+
+        let limits = SearchLimits {
+            max_depth: Some(depth),
+            ..Default::default()
+        };
+
+        let result = Search::think(board, limits, &tt);
+
+        total_nodes += result.nodes;
+    }
+
+    total_nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_runs_search_the_same_nodes() {
+        let first = total_nodes(4);
+
+        assert!(first > 0);
+        assert_eq!(total_nodes(4), first);
+    }
+}
@@ -0,0 +1,485 @@
+use crate::see::attackers_to;
+use chess::{
+    BitBoard, Board, BoardBuilder, CastleRights, ChessMove, Color, Error, File, MoveGen, Piece,
+    Square, ALL_COLORS, ALL_FILES, EMPTY,
+};
+use std::str::FromStr;
+
+const KING_SIDE: usize = 0;
+const QUEEN_SIDE: usize = 1;
+
+// where the king and the rook end up, whatever files they started on
+const CASTLED_FILES: [(File, File); 2] = [(File::G, File::F), (File::C, File::D)];
+
+// the board only holds rights for an e-file king with corner rooks, so the rook file of every
+// right is kept here and the board gets whichever of them it can represent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub board: Board,
+    rights: [[Option<File>; 2]; 2],
+}
+
+impl Position {
+    // reads shredder-fen rook files as well as x-fen KQkq, which mean the outermost rook
+    pub fn from_fen(fen: &str) -> Result<Position, Error> {
+        let invalid = || Error::InvalidFen {
+            fen: fen.to_string(),
+        };
+
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+
+        let castling = match fields.get(2) {
+            Some(castling) => *castling,
+            None => return Err(invalid()),
+        };
+
+        fields[2] = "-";
+
+        let board = Board::from_str(&fields.join(" "))?;
+
+        let mut rights = [[None; 2]; 2];
+
+        for right in castling.chars().filter(|&right| right != '-') {
+            let color = if right.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+
+            let king = board.king_square(color);
+
+            if king.get_rank() != color.to_my_backrank() {
+                continue;
+            }
+
+            let mut rooks = ALL_FILES
+                .into_iter()
+                .filter(|&file| is_own_rook(&board, color, file));
+
+            let rook_file = match right.to_ascii_lowercase() {
+                'k' => rooks.rfind(|&file| file > king.get_file()),
+                'q' => rooks.find(|&file| file < king.get_file()),
+                'a'..='h' => {
+                    let file = File::from_index(right.to_ascii_lowercase() as usize - 'a' as usize);
+
+                    is_own_rook(&board, color, file).then_some(file)
+                }
+                _ => return Err(invalid()),
+            };
+
+            // a right without its rook is dropped, as the board does with a standard fen
+            if let Some(rook_file) = rook_file {
+                let side = if rook_file > king.get_file() {
+                    KING_SIDE
+                } else {
+                    QUEEN_SIDE
+                };
+
+                rights[color.to_index()][side] = Some(rook_file);
+            }
+        }
+
+        Ok(Position::new(board, rights))
+    }
+
+    fn new(board: Board, rights: [[Option<File>; 2]; 2]) -> Position {
+        let mut builder = BoardBuilder::from(&board);
+
+        for color in ALL_COLORS {
+            let king_on_e = board.king_square(color).get_file() == File::E;
+
+            let [king_side, queen_side] = rights[color.to_index()];
+
+            let king_side = king_on_e && king_side == Some(File::H);
+            let queen_side = king_on_e && queen_side == Some(File::A);
+
+            let castle_rights = match (king_side, queen_side) {
+                (true, true) => CastleRights::Both,
+                (true, false) => CastleRights::KingSide,
+                (false, true) => CastleRights::QueenSide,
+                (false, false) => CastleRights::NoRights,
+            };
+
+            builder.castle_rights(color, castle_rights);
+        }
+
+        Position {
+            board: Board::try_from(builder).unwrap_or(board),
+            rights,
+        }
+    }
+
+    // castles are given as the king taking its own rook
+    pub fn legal_moves(&self) -> Vec<ChessMove> {
+        let board = &self.board;
+
+        let mut moves: Vec<ChessMove> = MoveGen::new_legal(board)
+            .filter(|&legal| !is_board_castle(board, legal))
+            .collect();
+
+        let color = board.side_to_move();
+
+        for side in [KING_SIDE, QUEEN_SIDE] {
+            if let Some(rook_file) = self.rights[color.to_index()][side] {
+                if self.can_castle(side, rook_file) {
+                    let rook = Square::make_square(color.to_my_backrank(), rook_file);
+
+                    moves.push(ChessMove::new(board.king_square(color), rook, None));
+                }
+            }
+        }
+
+        moves
+    }
+
+    pub fn make_move(&self, legal: ChessMove) -> Option<Position> {
+        self.legal_moves()
+            .contains(&legal)
+            .then(|| self.make_move_unchecked(legal))
+    }
+
+    fn make_move_unchecked(&self, legal: ChessMove) -> Position {
+        let board = &self.board;
+        let color = board.side_to_move();
+
+        let source = legal.get_source();
+        let dest = legal.get_dest();
+
+        let mut rights = self.rights;
+
+        if board.piece_on(source) == Some(Piece::King) {
+            rights[color.to_index()] = [None; 2];
+        }
+
+        if is_castle(board, legal) {
+            let side = if dest.get_file() > source.get_file() {
+                KING_SIDE
+            } else {
+                QUEEN_SIDE
+            };
+
+            let (king_file, rook_file) = CASTLED_FILES[side];
+            let rank = color.to_my_backrank();
+
+            let mut builder = BoardBuilder::from(board);
+
+            builder
+                .clear_square(source)
+                .clear_square(dest)
+                .piece(Square::make_square(rank, king_file), Piece::King, color)
+                .piece(Square::make_square(rank, rook_file), Piece::Rook, color)
+                .castle_rights(color, CastleRights::NoRights)
+                .side_to_move(!color)
+                .en_passant(None);
+
+            let castled = Board::try_from(builder).expect("a legal castle gives a legal board");
+
+            return Position::new(castled, rights);
+        }
+
+        // a rook that moves or gets taken takes its right with it
+        for color in ALL_COLORS {
+            for right in &mut rights[color.to_index()] {
+                if let Some(file) = *right {
+                    let rook = Square::make_square(color.to_my_backrank(), file);
+
+                    if source == rook || dest == rook {
+                        *right = None;
+                    }
+                }
+            }
+        }
+
+        Position::new(board.make_move_new(legal), rights)
+    }
+
+    fn can_castle(&self, side: usize, rook_file: File) -> bool {
+        let board = &self.board;
+        let color = board.side_to_move();
+        let rank = color.to_my_backrank();
+
+        if *board.checkers() != EMPTY {
+            return false;
+        }
+
+        let king = board.king_square(color);
+        let rook = Square::make_square(rank, rook_file);
+
+        let (king_file, castled_rook_file) = CASTLED_FILES[side];
+
+        let king_to = Square::make_square(rank, king_file);
+        let rook_to = Square::make_square(rank, castled_rook_file);
+
+        // everything the king and the rook cross or land on has to be empty apart from the two of them
+        let files = [king, king_to, rook, rook_to].map(|square| square.get_file().to_index());
+
+        let first = *files.iter().min().unwrap();
+        let last = *files.iter().max().unwrap();
+
+        let others = *board.combined() ^ BitBoard::from_square(king) ^ BitBoard::from_square(rook);
+
+        let is_blocked = (first..=last)
+            .map(|file| Square::make_square(rank, File::from_index(file)))
+            .any(|square| others & BitBoard::from_square(square) != EMPTY);
+
+        if is_blocked {
+            return false;
+        }
+
+        let their_pieces = board.color_combined(!color);
+
+        // the king may not pass through check, and moving the rook away may uncover one on the
+        // square it lands on
+        let step_files = if king_file > king.get_file() {
+            king.get_file().to_index()..king_file.to_index()
+        } else {
+            king_file.to_index() + 1..king.get_file().to_index() + 1
+        };
+
+        let passes_check = step_files
+            .map(|file| Square::make_square(rank, File::from_index(file)))
+            .any(|square| attackers_to(board, square, *board.combined()) & their_pieces != EMPTY);
+
+        let castled = others | BitBoard::from_square(king_to) | BitBoard::from_square(rook_to);
+
+        !passes_check && attackers_to(board, king_to, castled) & their_pieces == EMPTY
+    }
+}
+
+pub fn perft(position: &Position, depth: u8) -> u64 {
+    let moves = position.legal_moves();
+
+    match depth {
+        0 => 1,
+        1 => moves.len() as u64,
+        _ => moves
+            .into_iter()
+            .map(|legal| perft(&position.make_move_unchecked(legal), depth - 1))
+            .sum(),
+    }
+}
+
+pub fn divide(position: &Position, depth: u8) -> Vec<(ChessMove, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    position
+        .legal_moves()
+        .into_iter()
+        .map(|legal| {
+            (
+                legal,
+                perft(&position.make_move_unchecked(legal), depth - 1),
+            )
+        })
+        .collect()
+}
+
+// the search plays on the board, which castles with the king's own move, so only castles the board
+// can represent have a search form
+pub fn move_from_uci(board: &Board, legal: ChessMove) -> ChessMove {
+    let source = legal.get_source();
+    let dest = legal.get_dest();
+
+    let is_board_castle = is_castle(board, legal)
+        && source.get_file() == File::E
+        && matches!(dest.get_file(), File::H | File::A);
+
+    if !is_board_castle {
+        return legal;
+    }
+
+    let file = if dest.get_file() == File::H {
+        File::G
+    } else {
+        File::C
+    };
+
+    ChessMove::new(source, Square::make_square(source.get_rank(), file), None)
+}
+
+pub fn move_to_uci(board: &Board, legal: ChessMove) -> ChessMove {
+    if !is_board_castle(board, legal) {
+        return legal;
+    }
+
+    let source = legal.get_source();
+
+    let file = match legal.get_dest().get_file() {
+        File::G => File::H,
+        _ => File::A,
+    };
+
+    ChessMove::new(source, Square::make_square(source.get_rank(), file), None)
+}
+
+pub fn pv_to_uci(board: &Board, pv: &[ChessMove]) -> Vec<ChessMove> {
+    let mut board = *board;
+
+    pv.iter()
+        .map(|&legal| {
+            let translated = move_to_uci(&board, legal);
+
+            board = board.make_move_new(legal);
+
+            translated
+        })
+        .collect()
+}
+
+fn is_castle(board: &Board, legal: ChessMove) -> bool {
+    let source = legal.get_source();
+    let dest = legal.get_dest();
+
+    board.piece_on(source) == Some(Piece::King)
+        && board.piece_on(dest) == Some(Piece::Rook)
+        && board.color_on(dest) == board.color_on(source)
+}
+
+// a king only ever moves two files when it castles
+fn is_board_castle(board: &Board, legal: ChessMove) -> bool {
+    let source = legal.get_source().get_file().to_index();
+    let dest = legal.get_dest().get_file().to_index();
+
+    board.piece_on(legal.get_source()) == Some(Piece::King) && source.abs_diff(dest) == 2
+}
+
+fn is_own_rook(board: &Board, color: Color, file: File) -> bool {
+    let square = Square::make_square(color.to_my_backrank(), file);
+
+    board.piece_on(square) == Some(Piece::Rook) && board.color_on(square) == Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(position: &Position, moves: &str) -> Position {
+        moves.split_whitespace().fold(*position, |position, m| {
+            let legal = ChessMove::from_str(m).unwrap();
+
+            position
+                .make_move(legal)
+                .unwrap_or_else(|| panic!("{m} is illegal in {}", position.board))
+        })
+    }
+
+    fn castles(position: &Position) -> Vec<String> {
+        position
+            .legal_moves()
+            .into_iter()
+            .filter(|&legal| is_castle(&position.board, legal))
+            .map(|legal| legal.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn chess960_perft_matches_the_known_counts() {
+        // from the chess programming wiki's chess960 perft results, every one with castling rights
+        for (fen, nodes) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+
+            for (depth, nodes) in (1..).zip(nodes) {
+                assert_eq!(perft(&position, depth), nodes, "{fen} at depth {depth}");
+            }
+
+            let divide = divide(&position, 3);
+
+            assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), nodes[2]);
+        }
+    }
+
+    #[test]
+    fn standard_castling_counts_the_same_as_the_board() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = Board::from_str(fen).unwrap();
+
+        for castling in ["KQkq", "HAha"] {
+            let position = Position::from_fen(&fen.replace("KQkq", castling)).unwrap();
+
+            assert_eq!(position.board, board);
+            assert_eq!(perft(&position, 3), crate::perft::perft(&board, 3));
+        }
+    }
+
+    #[test]
+    fn the_king_takes_its_rook_to_castle() {
+        let position = Position::from_fen("1k6/8/8/8/8/8/8/RK2R3 w EA - 0 1").unwrap();
+
+        assert_eq!(castles(&position), ["b1e1", "b1a1"]);
+
+        let king_side = play(&position, "b1e1");
+        let queen_side = play(&position, "b1a1");
+
+        assert_eq!(
+            king_side.board,
+            Board::from_str("1k6/8/8/8/8/8/8/R4RK1 b - - 0 1").unwrap()
+        );
+        assert_eq!(
+            queen_side.board,
+            Board::from_str("1k6/8/8/8/8/8/8/2KRR3 b - - 0 1").unwrap()
+        );
+
+        // castling spends both rights
+        assert!(castles(&play(&king_side, "b8b7")).is_empty());
+
+        // the king's own move to the castled square is just an illegal king move
+        assert!(position
+            .make_move(ChessMove::from_str("b1g1").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn castling_needs_safe_and_empty_squares() {
+        // the f8 rook covers a square the king crosses on the king side only
+        let attacked = Position::from_fen("1k3r2/8/8/8/8/8/8/RK2R3 w EA - 0 1").unwrap();
+
+        assert_eq!(castles(&attacked), ["b1a1"]);
+
+        // the knight stands where the king side rook would land
+        let blocked = Position::from_fen("1k6/8/8/8/8/8/8/RK2RN2 w EA - 0 1").unwrap();
+
+        assert_eq!(castles(&blocked), ["b1a1"]);
+
+        // a rook that moves away and back has lost its right
+        let moved = play(
+            &Position::from_fen("1k6/8/8/8/8/8/8/RK2R3 w EA - 0 1").unwrap(),
+            "e1e2 b8b7 e2e1 b7b8",
+        );
+
+        assert_eq!(castles(&moved), ["b1a1"]);
+    }
+
+    #[test]
+    fn only_board_castles_translate_for_the_search() {
+        let board = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        for (uci, search) in [("e1h1", "e1g1"), ("e1a1", "e1c1")] {
+            let uci = ChessMove::from_str(uci).unwrap();
+            let search = ChessMove::from_str(search).unwrap();
+
+            assert_eq!(move_from_uci(&board, uci), search);
+            assert_eq!(move_to_uci(&board, search), uci);
+        }
+
+        // a king off the e-file castles in a way the board has no move for
+        let board = Board::from_str("1k6/8/8/8/8/8/8/RK2R3 w - - 0 1").unwrap();
+        let castle = ChessMove::from_str("b1a1").unwrap();
+
+        assert_eq!(move_from_uci(&board, castle), castle);
+    }
+}
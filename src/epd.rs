@@ -0,0 +1,212 @@
+use crate::{
+    search::{Search, SearchLimits},
+    tt::{TranspositionTable, DEFAULT_HASH_SIZE},
+};
+use chess::{Board, ChessMove};
+use std::str::FromStr;
+
+const DEFAULT_DEPTH: u8 = 8;
+
+pub fn run(path: &str, depth: Option<u8>) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+
+            return;
+        }
+    };
+
+    let mut solved = 0;
+    let mut total = 0;
+
+    for result in solve_suite(&text, depth.unwrap_or(DEFAULT_DEPTH)) {
+        let Outcome {
+            id,
+            best_move,
+            is_solved,
+        } = match result {
+            Ok(outcome) => outcome,
+            Err(number) => {
+                eprintln!("{path}:{number}: unreadable epd");
+
+                continue;
+            }
+        };
+
+        let best_move = best_move.map_or(String::from("(none)"), |best_move| best_move.to_string());
+
+        println!(
+            "{id}: {best_move} {}",
+            if is_solved { "solved" } else { "failed" }
+        );
+
+        solved += is_solved as u32;
+        total += 1;
+    }
+
+    println!("solved {solved}/{total}");
+}
+
+struct Outcome {
+    id: String,
+    best_move: Option<ChessMove>,
+    is_solved: bool,
+}
+
+// lazy, so each result can be printed as soon as its search is done. an unreadable position is
+// reported by its line number
+fn solve_suite(text: &str, depth: u8) -> impl Iterator<Item = Result<Outcome, usize>> + '_ {
+    let mut tt = TranspositionTable::new(DEFAULT_HASH_SIZE);
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(move |(number, line)| {
+            let position = parse_line(line).ok_or(number + 1)?;
+
+            tt.clear();
+
+            let limits = SearchLimits {
+                max_depth: Some(depth),
+                ..Default::default()
+            };
+
+            let result = Search::think(position.board, limits, &tt);
+
+            let is_solved = result.best_move.is_some_and(|best_move| {
+                (position.best_moves.is_empty() || position.best_moves.contains(&best_move))
+                    && !position.avoid_moves.contains(&best_move)
+            });
+
+            let id = position.id.unwrap_or_else(|| format!("#{}", number + 1));
+
+            Ok(Outcome {
+                id,
+                best_move: result.best_move,
+                is_solved,
+            })
+        })
+}
+
+struct EpdPosition {
+    board: Board,
+    best_moves: Vec<ChessMove>,
+    avoid_moves: Vec<ChessMove>,
+    id: Option<String>,
+}
+
+fn parse_line(line: &str) -> Option<EpdPosition> {
+    let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+
+    let board = Board::from_str(&fields.join(" ")).ok()?;
+
+    let mut best_moves = Vec::new();
+    let mut avoid_moves = Vec::new();
+    let mut id = None;
+
+    let operations: String = line
+        .split_whitespace()
+        .skip(4)
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    for operation in operations.split(';') {
+        let mut tokens = operation.split_whitespace();
+
+        match tokens.next() {
+            Some("bm") => best_moves = parse_moves(&board, tokens)?,
+            Some("am") => avoid_moves = parse_moves(&board, tokens)?,
+            Some("id") => id = Some(tokens.collect::<Vec<&str>>().join(" ").replace('"', "")),
+            _ => {}
+        }
+    }
+
+    if best_moves.is_empty() && avoid_moves.is_empty() {
+        return None;
+    }
+
+    Some(EpdPosition {
+        board,
+        best_moves,
+        avoid_moves,
+        id,
+    })
+}
+
+// epd moves are usually san, but plenty of suites use coordinate notation
+fn parse_moves<'a>(board: &Board, tokens: impl Iterator<Item = &'a str>) -> Option<Vec<ChessMove>> {
+    tokens
+        .map(|token| {
+            ChessMove::from_san(board, token)
+                .ok()
+                .or_else(|| ChessMove::from_str(token).ok())
+                .filter(|&legal| board.legal(legal))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(line: &str) -> Vec<ChessMove> {
+        line.split_whitespace()
+            .map(|m| ChessMove::from_str(m).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn bm_am_and_id_operations_parse() {
+        let position =
+            parse_line("6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; am a1a7 Ra2; id \"back rank\";")
+                .unwrap();
+
+        assert_eq!(position.best_moves, moves("a1a8"));
+        assert_eq!(position.avoid_moves, moves("a1a7 a1a2"));
+        assert_eq!(position.id.as_deref(), Some("back rank"));
+
+        // several best moves, and coordinate notation
+        let position = parse_line("4k3/8/8/8/8/8/8/R3K2R w KQ - bm O-O e1c1;").unwrap();
+
+        assert_eq!(position.best_moves, moves("e1g1 e1c1"));
+        assert_eq!(position.id, None);
+
+        // nothing to check against, a move that is not legal here, and not a position at all
+        for line in [
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - id \"no moves\";",
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Rh8;",
+            "bm Ra8#;",
+        ] {
+            assert!(parse_line(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn easy_tactics_are_solved_at_a_low_depth() {
+        let suite = "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"mate\";\n\
+                     \n\
+                     3q3k/p5pp/8/4N3/8/4B3/5PPP/6K1 w - - bm Nf7+; id \"fork\";\n\
+                     4k3/8/8/3q4/8/8/3R4/4K3 w - - bm Rxd5; id \"hanging queen\";\n\
+                     not a position\n\
+                     4k3/8/8/8/8/2p5/1p6/1R2K3 w - - am Rxb2; id \"defended pawn\";\n";
+
+        let ids: Vec<_> = solve_suite(suite, 4)
+            .map(|result| match result {
+                Ok(outcome) => Ok((outcome.id, outcome.is_solved)),
+                Err(number) => Err(number),
+            })
+            .collect();
+
+        assert_eq!(
+            ids,
+            [
+                Ok((String::from("mate"), true)),
+                Ok((String::from("fork"), true)),
+                Ok((String::from("hanging queen"), true)),
+                Err(5),
+                Ok((String::from("defended pawn"), true)),
+            ]
+        );
+    }
+}
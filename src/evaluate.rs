@@ -1,124 +1,784 @@
-use chess::{Board, Color, Piece, Square};
+use chess::{
+    get_adjacent_files, get_bishop_moves, get_file, get_king_moves, get_knight_moves,
+    get_pawn_attacks, get_rank, get_rook_moves, BitBoard, Board, Color, File, Piece, Rank, Square,
+    ALL_COLORS, ALL_FILES, ALL_PIECES, EMPTY,
+};
+
+use crate::{params::EvalParams, search::INFINITY};
 
 pub type Eval = i16;
 
-pub fn evaluate(board: &Board) -> Eval {
-    let mut score = 0;
+#[derive(Debug)]
+pub struct EvalBreakdown {
+    pub material: i32,
+    pub piece_square: [i32; 6],
+    pub mobility: i32,
+    pub passed_pawns: i32,
+    pub pawn_structure: i32,
+    pub bishop_pair: i32,
+    pub king_safety: i32,
+    pub rooks: i32,
+    pub tempo: Eval,
+    pub scale: Eval,
+    pub phase: i32,
+    pub score: Eval,
+}
+
+// the search keeps phase and material up to date move by move, so both are passed in rather than
+// counted again on every call
+pub fn evaluate(board: &Board, phase: i32, material: i32, params: &EvalParams) -> Eval {
+    explain(board, phase, material, params).score
+}
+
+// the terms add up in i32, since loaded weights can be anything an eval holds, and the score is
+// clamped short of the mate scores
+pub fn explain(board: &Board, phase: i32, material: i32, params: &EvalParams) -> EvalBreakdown {
+    let mut piece_square_mg = [0; 6];
+    let mut piece_square_eg = [0; 6];
+    let mut mobility = 0;
 
-    let is_endgame = is_endgame(board);
+    let phase = phase.min(MAX_PHASE);
 
     for square in *board.combined() {
         let piece = board.piece_on(square).unwrap();
         let piece_colour = board.color_on(square).unwrap();
 
-        let piece_score = match piece {
-            Piece::Pawn => 100,
-            Piece::Knight => 320,
-            Piece::Bishop => 330,
-            Piece::Rook => 500,
-            Piece::Queen => 900,
-            Piece::King => 20000,
-        } + piece_square(piece, piece_colour, square, is_endgame);
-
-        score += match piece_colour {
-            Color::White => piece_score,
-            Color::Black => -piece_score,
+        let (piece_square_mg_value, piece_square_eg_value) =
+            piece_square(piece, piece_colour, square, params);
+
+        let sign = match piece_colour {
+            Color::White => 1,
+            Color::Black => -1,
         };
+
+        piece_square_mg[piece.to_index()] += sign * piece_square_mg_value as i32;
+        piece_square_eg[piece.to_index()] += sign * piece_square_eg_value as i32;
+        mobility += sign * piece_mobility(board, piece, piece_colour, square, params);
     }
 
-    match board.side_to_move() {
+    let mut piece_square_scores = [0; 6];
+
+    for (index, score) in piece_square_scores.iter_mut().enumerate() {
+        *score = taper(piece_square_mg[index], piece_square_eg[index], phase);
+    }
+
+    let (passed_pawns_mg, passed_pawns_eg) = passed_pawns(board, params);
+    let passed_pawns = taper(passed_pawns_mg, passed_pawns_eg, phase);
+    let pawn_structure = pawn_structure(board, params);
+    let bishop_pair = bishop_pair(board, params);
+    let king_safety = taper(king_safety(board, params), 0, phase);
+    let rooks = rook_placement(board, params);
+
+    let score = material
+        + piece_square_scores.iter().sum::<i32>()
+        + mobility
+        + passed_pawns
+        + pawn_structure
+        + bishop_pair
+        + king_safety
+        + rooks;
+
+    let scale = endgame_scale(board, params);
+
+    let score = score * scale as i32 / FULL_SCALE as i32;
+
+    // everything above is from white's side, the tempo belongs to whoever moves next
+    let score = match board.side_to_move() {
         Color::White => score,
         Color::Black => -score,
+    } + params.tempo as i32;
+
+    EvalBreakdown {
+        material,
+        piece_square: piece_square_scores,
+        mobility,
+        passed_pawns,
+        pawn_structure,
+        bishop_pair,
+        king_safety,
+        rooks,
+        tempo: params.tempo,
+        scale,
+        phase,
+        score: score.clamp(-MAX_EVAL, MAX_EVAL) as Eval,
     }
 }
 
-fn piece_square(piece: Piece, piece_colour: Color, square: Square, is_endgame: bool) -> Eval {
-    let table = match piece {
-        Piece::Pawn => PAWN_TABLE,
-        Piece::Knight => KNIGHT_TABLE,
-        Piece::Bishop => BISHOP_TABLE,
-        Piece::Rook => ROOK_TABLE,
-        Piece::Queen => QUEEN_TABLE,
-        Piece::King => {
-            if is_endgame {
-                KING_TABLE_ENDGAME
-            } else {
-                KING_TABLE
-            }
-        }
-    };
+fn piece_square(
+    piece: Piece,
+    piece_colour: Color,
+    square: Square,
+    params: &EvalParams,
+) -> (Eval, Eval) {
+    let mg_table = &params.piece_square_mg[piece.to_index()];
+    let eg_table = &params.piece_square_eg[piece.to_index()];
 
+    // tables are laid out as printed, a8 first, so white flips the rank and black reads them as is
     let index = match piece_colour {
-        Color::White => 63 - square.to_index(),
+        Color::White => square.to_index() ^ 56,
         Color::Black => square.to_index(),
     };
 
-    table[index]
-}
-
-const PAWN_TABLE: [Eval; 64] = [
-    0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10, 5, 5,
-    10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 20, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5, 10, 10, -20,
-    -20, 10, 10, 5, 0, 0, 0, 0, 0, 0, 0, 0,
-];
-
-const KNIGHT_TABLE: [Eval; 64] = [
-    -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 0, 0, 0, -20, -40, -30, 0, 10, 15, 15, 10,
-    0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 15, 20, 20, 15, 0, -30, -30, 5, 10, 15, 15, 10,
-    5, -30, -40, -20, 0, 5, 5, 0, -20, -40, -50, -40, -30, -30, -30, -30, -40, -50,
-];
-
-const BISHOP_TABLE: [Eval; 64] = [
-    -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 10, 10, 5, 0,
-    -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10, 10, 10, 10, 10, 10, 10,
-    -10, -10, 5, 0, 0, 0, 0, 5, -10, -20, -10, -10, -10, -10, -10, -10, -20,
-];
-
-const ROOK_TABLE: [Eval; 64] = [
-    0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
-    0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 0, 0,
-    0, 5, 5, 0, 0, 0,
-];
-
-const QUEEN_TABLE: [Eval; 64] = [
-    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
-    -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10, 0, 5, 0, 0,
-    0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
-];
-
-const KING_TABLE: [Eval; 64] = [
-    -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40,
-    -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -20, -30, -30, -40, -40, -30,
-    -30, -20, -10, -20, -20, -20, -20, -20, -20, -10, 20, 20, 0, 0, 0, 0, 20, 20, 20, 30, 10, 0, 0,
-    10, 30, 20,
-];
-
-const KING_TABLE_ENDGAME: [Eval; 64] = [
-    -50, -40, -30, -20, -20, -30, -40, -50, -30, -20, -10, 0, 0, -10, -20, -30, -30, -10, 20, 30,
-    30, 20, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30,
-    -10, 20, 30, 30, 20, -10, -30, -30, -30, 0, 0, 0, 0, -30, -30, -50, -30, -30, -30, -30, -30,
-    -30, -50,
-];
-
-fn is_endgame(board: &Board) -> bool {
-    if board.pieces(Piece::Queen).popcnt() == 0 {
-        true
+    (mg_table[index], eg_table[index])
+}
+
+pub fn game_phase(board: &Board) -> i32 {
+    [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .map(|piece| board.pieces(piece).popcnt() as i32 * piece_phase(piece))
+        .sum()
+}
+
+// white's material minus black's, the kings cancel out
+pub fn material(board: &Board, params: &EvalParams) -> i32 {
+    ALL_PIECES
+        .into_iter()
+        .map(|piece| {
+            let count = |colour| (board.pieces(piece) & board.color_combined(colour)).popcnt();
+
+            (count(Color::White) as i32 - count(Color::Black) as i32)
+                * params.piece_values[piece.to_index()] as i32
+        })
+        .sum()
+}
+
+pub fn piece_phase(piece: Piece) -> i32 {
+    match piece {
+        Piece::Knight | Piece::Bishop => 1,
+        Piece::Rook => 2,
+        Piece::Queen => 4,
+        Piece::Pawn | Piece::King => 0,
+    }
+}
+
+fn taper(mg: i32, eg: i32, phase: i32) -> i32 {
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+fn piece_mobility(
+    board: &Board,
+    piece: Piece,
+    piece_colour: Color,
+    square: Square,
+    params: &EvalParams,
+) -> i32 {
+    let weight = params.mobility[piece.to_index()] as i32;
+
+    let targets =
+        piece_attacks(piece, square, *board.combined()) & !board.color_combined(piece_colour);
+
+    targets.popcnt() as i32 * weight
+}
+
+fn piece_attacks(piece: Piece, square: Square, blockers: BitBoard) -> BitBoard {
+    match piece {
+        Piece::Knight => get_knight_moves(square),
+        Piece::Bishop => get_bishop_moves(square, blockers),
+        Piece::Rook => get_rook_moves(square, blockers),
+        Piece::Queen => get_bishop_moves(square, blockers) | get_rook_moves(square, blockers),
+        Piece::Pawn | Piece::King => EMPTY,
+    }
+}
+
+fn passed_pawns(board: &Board, params: &EvalParams) -> (i32, i32) {
+    let mut mg = 0;
+    let mut eg = 0;
+
+    for colour in ALL_COLORS {
+        let pawns = board.pieces(Piece::Pawn) & board.color_combined(colour);
+        let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!colour);
+
+        for square in pawns {
+            let file = square.get_file();
+            let span = (get_file(file) | get_adjacent_files(file)) & forward_mask(square, colour);
+
+            if span & enemy_pawns == EMPTY {
+                let rank = relative_rank(square, colour);
+
+                let sign = match colour {
+                    Color::White => 1,
+                    Color::Black => -1,
+                };
+
+                mg += sign * params.passed_pawn_bonus_mg[rank] as i32;
+                eg += sign * params.passed_pawn_bonus_eg[rank] as i32;
+            }
+        }
+    }
+
+    (mg, eg)
+}
+
+fn pawn_structure(board: &Board, params: &EvalParams) -> i32 {
+    let mut score = 0;
+
+    for colour in ALL_COLORS {
+        let pawns = board.pieces(Piece::Pawn) & board.color_combined(colour);
+        let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!colour);
+
+        let mut penalty = 0;
+
+        for file in ALL_FILES {
+            let pawns_on_file = (pawns & get_file(file)).popcnt() as i32;
+
+            if pawns_on_file > 1 {
+                penalty += params.doubled_pawn_penalty as i32 * (pawns_on_file - 1);
+            }
+        }
+
+        for square in pawns {
+            let adjacent_pawns = pawns & get_adjacent_files(square.get_file());
+
+            if adjacent_pawns == EMPTY {
+                penalty += params.isolated_pawn_penalty as i32;
+
+                continue;
+            }
+
+            let can_be_defended = adjacent_pawns & !forward_mask(square, colour) != EMPTY;
+
+            let is_blocked = square.forward(colour).is_some_and(|stop| {
+                enemy_pawns & BitBoard::from_square(stop) != EMPTY
+                    || get_pawn_attacks(stop, colour, enemy_pawns) != EMPTY
+            });
+
+            if !can_be_defended && is_blocked {
+                penalty += params.backward_pawn_penalty as i32;
+            }
+        }
+
+        score += match colour {
+            Color::White => -penalty,
+            Color::Black => penalty,
+        };
+    }
+
+    score
+}
+
+fn bishop_pair(board: &Board, params: &EvalParams) -> i32 {
+    let has_pair =
+        |colour| (board.pieces(Piece::Bishop) & board.color_combined(colour)).popcnt() >= 2;
+
+    let mut score = 0;
+
+    if has_pair(Color::White) {
+        score += params.bishop_pair_bonus as i32;
+    }
+
+    if has_pair(Color::Black) {
+        score -= params.bishop_pair_bonus as i32;
+    }
+
+    score
+}
+
+// a bishop each on opposite colours and nothing else but pawns is hard to win even a pawn or two up
+fn endgame_scale(board: &Board, params: &EvalParams) -> Eval {
+    let others =
+        board.pieces(Piece::Knight) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+
+    let bishops = board.pieces(Piece::Bishop);
+
+    let white_bishops = bishops & board.color_combined(Color::White);
+    let black_bishops = bishops & board.color_combined(Color::Black);
+
+    if others != EMPTY || white_bishops.popcnt() != 1 || black_bishops.popcnt() != 1 {
+        return FULL_SCALE;
+    }
+
+    let is_opposite =
+        is_light_square(white_bishops.to_square()) != is_light_square(black_bishops.to_square());
+
+    let pawns = board.pieces(Piece::Pawn);
+
+    let pawn_difference = (pawns & board.color_combined(Color::White)).popcnt() as i32
+        - (pawns & board.color_combined(Color::Black)).popcnt() as i32;
+
+    if is_opposite && pawn_difference.abs() <= OPPOSITE_BISHOPS_MAX_PAWN_DIFFERENCE {
+        params.opposite_bishops_scale
     } else {
-        let knights = board.pieces(Piece::Knight);
-        let bishops = board.pieces(Piece::Bishop);
-        let knights_and_bishops = knights | bishops;
-        let rooks = board.pieces(Piece::Rook);
-
-        let white = board.color_combined(Color::White);
-        let white_minor_pieces = (knights_and_bishops & white).popcnt();
-        let white_rooks = (rooks & white).popcnt();
-        let white_endgame = white_minor_pieces <= 1 && white_rooks == 0;
-
-        let black = board.color_combined(Color::Black);
-        let black_minor_pieces = (knights_and_bishops & black).popcnt();
-        let black_rooks = (rooks & black).popcnt();
-        let black_endgame = black_minor_pieces <= 1 && black_rooks == 0;
-
-        white_endgame && black_endgame
+        FULL_SCALE
+    }
+}
+
+pub fn is_light_square(square: Square) -> bool {
+    (square.get_rank().to_index() + square.get_file().to_index()) % 2 == 1
+}
+
+fn king_safety(board: &Board, params: &EvalParams) -> i32 {
+    let mut score = 0;
+
+    for colour in ALL_COLORS {
+        let king_square = board.king_square(colour);
+        let pawns = board.pieces(Piece::Pawn) & board.color_combined(colour);
+
+        let mut penalty = 0;
+
+        let king_file = king_square.get_file();
+        let is_castled =
+            relative_rank(king_square, colour) <= 1 && king_file != File::D && king_file != File::E;
+
+        if is_castled {
+            let shield_ranks = (1..=2)
+                .map(|offset| relative_rank(king_square, colour) + offset)
+                .map(|rank| match colour {
+                    Color::White => get_rank(Rank::from_index(rank)),
+                    Color::Black => get_rank(Rank::from_index(7 - rank)),
+                })
+                .fold(EMPTY, |mask, rank| mask | rank);
+
+            let shield_files = get_file(king_file) | get_adjacent_files(king_file);
+
+            for file in ALL_FILES {
+                let file_mask = get_file(file) & shield_files;
+
+                if file_mask != EMPTY && pawns & file_mask & shield_ranks == EMPTY {
+                    penalty += params.missing_shield_pawn_penalty as i32;
+                }
+            }
+        }
+
+        let king_zone = get_king_moves(king_square) | BitBoard::from_square(king_square);
+        let blockers = *board.combined();
+
+        let attackers = board
+            .color_combined(!colour)
+            .into_iter()
+            .filter(|&square| {
+                let piece = board.piece_on(square).unwrap();
+
+                piece_attacks(piece, square, blockers) & king_zone != EMPTY
+            })
+            .count();
+
+        penalty += params.king_attacker_penalty
+            [attackers.min(params.king_attacker_penalty.len() - 1)] as i32;
+
+        score += match colour {
+            Color::White => -penalty,
+            Color::Black => penalty,
+        };
+    }
+
+    score
+}
+
+fn rook_placement(board: &Board, params: &EvalParams) -> i32 {
+    let all_pawns = board.pieces(Piece::Pawn);
+
+    let mut score = 0;
+
+    for colour in ALL_COLORS {
+        let rooks = board.pieces(Piece::Rook) & board.color_combined(colour);
+        let pawns = all_pawns & board.color_combined(colour);
+
+        let mut bonus = 0;
+
+        for square in rooks {
+            let file = get_file(square.get_file());
+
+            if all_pawns & file == EMPTY {
+                bonus += params.rook_open_file_bonus as i32;
+            } else if pawns & file == EMPTY {
+                bonus += params.rook_semi_open_file_bonus as i32;
+            }
+
+            if relative_rank(square, colour) == 6 {
+                bonus += params.rook_seventh_rank_bonus as i32;
+            }
+        }
+
+        score += match colour {
+            Color::White => bonus,
+            Color::Black => -bonus,
+        };
+    }
+
+    score
+}
+
+fn forward_mask(square: Square, colour: Color) -> BitBoard {
+    let rank = square.get_rank().to_index() as u32;
+
+    match colour {
+        Color::White => BitBoard((!0u64).checked_shl(8 * (rank + 1)).unwrap_or(0)),
+        Color::Black => BitBoard((1u64 << (8 * rank)) - 1),
+    }
+}
+
+fn relative_rank(square: Square, colour: Color) -> usize {
+    match colour {
+        Color::White => square.get_rank().to_index(),
+        Color::Black => 7 - square.get_rank().to_index(),
+    }
+}
+
+const MAX_PHASE: i32 = 24;
+
+// anything past half of infinity reads as a mate score to the search
+const MAX_EVAL: i32 = INFINITY as i32 / 2 - 1;
+
+// endgame scales are out of this, so full scale leaves the score alone
+pub const FULL_SCALE: Eval = 128;
+
+const OPPOSITE_BISHOPS_MAX_PAWN_DIFFERENCE: i32 = 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::{Rng, DEFAULT_SEED};
+    use chess::{BoardBuilder, MoveGen};
+    use std::str::FromStr;
+
+    const PLAYOUTS: usize = 20;
+    const PLAYOUT_PLIES: usize = 120;
+
+    fn explain_fen(fen: &str) -> EvalBreakdown {
+        let board = Board::from_str(fen).unwrap();
+        let params = EvalParams::default();
+
+        explain(
+            &board,
+            game_phase(&board),
+            material(&board, &params),
+            &params,
+        )
+    }
+
+    #[test]
+    fn the_breakdown_adds_up_to_the_score() {
+        for (fen, sign) in [
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                1,
+            ),
+            (
+                "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+                -1,
+            ),
+            ("8/5k2/3b4/2p5/2P5/1B6/5K2/8 w - - 0 1", 1),
+        ] {
+            let breakdown = explain_fen(fen);
+
+            let white = breakdown.material
+                + breakdown.piece_square.iter().sum::<i32>()
+                + breakdown.mobility
+                + breakdown.passed_pawns
+                + breakdown.pawn_structure
+                + breakdown.bishop_pair
+                + breakdown.king_safety
+                + breakdown.rooks;
+
+            let score = sign * (white * breakdown.scale as i32 / FULL_SCALE as i32)
+                + breakdown.tempo as i32;
+
+            assert_eq!(breakdown.score as i32, score, "{fen}");
+        }
+    }
+
+    // a colour-flipped board is the same position for the other side, so the side to move must
+    // score exactly the same, tempo included
+    #[test]
+    fn mirrored_positions_score_the_same() {
+        let params = EvalParams::default();
+
+        let mut rng = Rng::new(DEFAULT_SEED);
+
+        for _ in 0..PLAYOUTS {
+            let mut board = Board::default();
+
+            for _ in 0..PLAYOUT_PLIES {
+                let mirrored = mirror(&board);
+
+                assert_eq!(
+                    evaluate(
+                        &board,
+                        game_phase(&board),
+                        material(&board, &params),
+                        &params
+                    ),
+                    evaluate(
+                        &mirrored,
+                        game_phase(&mirrored),
+                        material(&mirrored, &params),
+                        &params
+                    ),
+                    "{board}"
+                );
+
+                let moves: Vec<_> = MoveGen::new_legal(&board).collect();
+
+                if moves.is_empty() {
+                    break;
+                }
+
+                board = board.make_move_new(moves[rng.below(moves.len() as u64) as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn mobility_counts_squares_not_held_by_own_pieces() {
+        let knight = EvalParams::default().mobility[Piece::Knight.to_index()] as i32;
+        let rook = EvalParams::default().mobility[Piece::Rook.to_index()] as i32;
+
+        // a centre knight reaches eight squares, one in the corner two
+        assert_eq!(
+            explain_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").mobility,
+            8 * knight
+        );
+        assert_eq!(
+            explain_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").mobility,
+            2 * knight
+        );
+
+        // the rook's file stops at its own pawn, along the rank it reaches up to the black knight
+        // and can take it. the knight gets four squares of its own back
+        assert_eq!(
+            explain_fen("4k3/8/8/8/8/8/P7/R2nK3 w - - 0 1").mobility,
+            3 * rook - 4 * knight
+        );
+    }
+
+    #[test]
+    fn only_pawns_with_no_enemy_pawn_ahead_are_passed() {
+        let params = EvalParams::default();
+
+        // each pawn stands on the other's neighbouring file, ahead of it
+        assert_eq!(
+            explain_fen("4k3/3p4/8/4P3/8/8/8/4K3 w - - 0 1").passed_pawns,
+            0
+        );
+
+        // once they have run past each other both are free, the black pawn a rank further on
+        assert_eq!(
+            explain_fen("4k3/8/8/4P3/8/3p4/8/4K3 w - - 0 1").passed_pawns,
+            (params.passed_pawn_bonus_eg[4] - params.passed_pawn_bonus_eg[5]) as i32
+        );
+
+        // with no pieces left the endgame bonus is all there is, and it grows up the board
+        assert_eq!(
+            explain_fen("4k3/8/4P3/8/8/8/p7/4K3 w - - 0 1").passed_pawns,
+            (params.passed_pawn_bonus_eg[5] - params.passed_pawn_bonus_eg[6]) as i32
+        );
+
+        // pawns only ever stand on the second to seventh ranks
+        let reachable = &params.passed_pawn_bonus_eg[1..7];
+
+        assert!(reachable.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn pawn_weaknesses_are_penalised() {
+        let params = EvalParams::default();
+
+        let doubled = params.doubled_pawn_penalty as i32;
+        let isolated = params.isolated_pawn_penalty as i32;
+        let backward = params.backward_pawn_penalty as i32;
+
+        // two pawns on one file with none beside them, each one isolated
+        assert_eq!(
+            explain_fen("4k3/8/8/8/4P3/4P3/8/4K3 w - - 0 1").pawn_structure,
+            -(doubled + 2 * isolated)
+        );
+
+        // d3 can't be defended by the pawn ahead of it on c4 and can't advance past e5, which
+        // is isolated itself
+        assert_eq!(
+            explain_fen("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1").pawn_structure,
+            isolated - backward
+        );
+
+        // connected pawns side by side are fine
+        assert_eq!(
+            explain_fen("4k3/8/8/8/3PP3/8/8/4K3 w - - 0 1").pawn_structure,
+            0
+        );
+    }
+
+    #[test]
+    fn only_a_pair_of_bishops_gets_the_bonus() {
+        let bonus = EvalParams::default().bishop_pair_bonus as i32;
+
+        for (fen, pair) in [
+            ("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", bonus),
+            ("2b1kb2/8/8/8/8/8/8/4K3 w - - 0 1", -bonus),
+            ("2b1kb2/8/8/8/8/8/8/2B1KB2 w - - 0 1", 0),
+            ("2b1k3/8/8/8/8/8/8/2B1KN2 w - - 0 1", 0),
+        ] {
+            assert_eq!(explain_fen(fen).bishop_pair, pair, "{fen}");
+        }
+    }
+
+    #[test]
+    fn pawns_are_rewarded_for_advancing() {
+        let pawns = |fen| explain_fen(fen).piece_square[Piece::Pawn.to_index()];
+
+        // piece-square scores are from white's side, so black wants them lower
+        assert!(pawns("7k/4P3/8/8/8/8/8/K7 w - - 0 1") > pawns("7k/8/8/8/8/8/4P3/K7 w - - 0 1"));
+        assert!(pawns("7k/8/8/8/8/8/4p3/K7 w - - 0 1") < pawns("7k/4p3/8/8/8/8/8/K7 w - - 0 1"));
+    }
+
+    #[test]
+    fn the_phase_blends_middlegame_into_endgame() {
+        assert_eq!(taper(100, -20, MAX_PHASE), 100);
+        assert_eq!(taper(100, -20, 0), -20);
+        assert_eq!(taper(100, -20, MAX_PHASE / 2), 40);
+
+        let params = EvalParams::default();
+
+        // kings only, so the king tables are the whole piece-square score
+        let board = Board::from_str("8/8/8/8/8/8/8/2k3K1 w - - 0 1").unwrap();
+
+        let king = |phase| explain(&board, phase, 0, &params).piece_square[Piece::King.to_index()];
+
+        let g1 = Square::G1.to_index() ^ 56;
+        let c1 = Square::C1.to_index();
+
+        let table =
+            |tables: &[[Eval; 64]; 6], index: usize| tables[Piece::King.to_index()][index] as i32;
+
+        assert_eq!(
+            king(MAX_PHASE),
+            table(&params.piece_square_mg, g1) - table(&params.piece_square_mg, c1)
+        );
+        assert_eq!(
+            king(0),
+            table(&params.piece_square_eg, g1) - table(&params.piece_square_eg, c1)
+        );
+
+        // promotions can take the count past a full board, which still only counts as one
+        assert_eq!(explain(&board, MAX_PHASE + 8, 0, &params).phase, MAX_PHASE);
+        assert_eq!(king(MAX_PHASE + 8), king(MAX_PHASE));
+    }
+
+    #[test]
+    fn a_bare_or_attacked_king_is_penalised_in_the_middlegame() {
+        let params = EvalParams::default();
+
+        let missing = params.missing_shield_pawn_penalty as i32;
+        let attackers = params.king_attacker_penalty.map(i32::from);
+
+        let king_safety = |fen: &str, phase: i32| {
+            let board = Board::from_str(fen).unwrap();
+
+            explain(&board, phase, material(&board, &params), &params).king_safety
+        };
+
+        // both castled behind a full shield, a rank further up still shields
+        assert_eq!(
+            king_safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1", MAX_PHASE),
+            0
+        );
+        assert_eq!(
+            king_safety("6k1/5ppp/8/8/8/5PPP/8/6K1 w - - 0 1", MAX_PHASE),
+            0
+        );
+
+        // white's shield is gone, one penalty per open file beside the king
+        let bare = "6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1";
+
+        assert_eq!(king_safety(bare, MAX_PHASE), -3 * missing);
+
+        // with the queens off the king is meant to come out, so the term fades away
+        assert_eq!(king_safety(bare, 0), 0);
+
+        // a rook along the second rank reaches f2, next to the white king
+        assert_eq!(
+            king_safety("6k1/5ppp/8/8/8/8/r4PPP/6K1 w - - 0 1", MAX_PHASE),
+            attackers[0] - attackers[1]
+        );
+    }
+
+    #[test]
+    fn rooks_score_open_files_and_the_seventh() {
+        let params = EvalParams::default();
+
+        let open = params.rook_open_file_bonus as i32;
+        let semi_open = params.rook_semi_open_file_bonus as i32;
+        let seventh = params.rook_seventh_rank_bonus as i32;
+
+        for (fen, rooks) in [
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", open),
+            ("4k3/p7/8/8/8/8/8/R3K3 w - - 0 1", semi_open),
+            ("4k3/8/8/8/8/8/P7/R3K3 w - - 0 1", 0),
+            ("4k3/R7/8/8/8/8/8/4K3 w - - 0 1", open + seventh),
+            // the seventh is black's second rank
+            ("4k3/8/8/8/8/8/r7/4K3 w - - 0 1", -(open + seventh)),
+            // black's own pawn closes the file it leaves half open for white
+            ("r3k3/p7/8/8/8/8/8/R3K3 w - - 0 1", semi_open),
+        ] {
+            assert_eq!(explain_fen(fen).rooks, rooks, "{fen}");
+        }
+    }
+
+    #[test]
+    fn opposite_bishops_damp_an_extra_pawn() {
+        // white's bishop is on a dark square, black's on c8 is light and on f8 dark
+        let opposite = explain_fen("2b3k1/5ppp/8/8/8/8/P4PPP/2B3K1 w - - 0 1");
+        let same = explain_fen("5bk1/5ppp/8/8/8/8/P4PPP/2B3K1 w - - 0 1");
+
+        let tempo = EvalParams::default().tempo as i32;
+
+        assert!(opposite.scale < FULL_SCALE);
+        assert_eq!(same.scale, FULL_SCALE);
+        assert!(same.score as i32 > tempo);
+        assert!((opposite.score as i32 - tempo) * 2 < same.score as i32 - tempo);
+
+        // anything besides the bishops is a normal endgame again
+        let with_knights = explain_fen("2b3k1/4nppp/8/8/8/8/P3NPPP/2B3K1 w - - 0 1");
+
+        assert_eq!(with_knights.scale, FULL_SCALE);
+    }
+
+    #[test]
+    fn the_side_to_move_gets_the_tempo() {
+        let tempo = EvalParams::default().tempo;
+
+        assert!(tempo > 0);
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 b - - 0 1",
+        ] {
+            assert_eq!(explain_fen(fen).score, tempo, "{fen}");
+        }
+
+        // anywhere else, handing over the move flips everything but the tempo
+        let white =
+            explain_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1");
+        let black =
+            explain_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b - - 0 1");
+
+        assert_ne!(white.score, tempo);
+        assert_eq!(white.score - tempo, -(black.score - tempo));
+    }
+
+    // flips the board vertically and swaps the colours
+    fn mirror(board: &Board) -> Board {
+        let mut builder = BoardBuilder::new();
+
+        for square in *board.combined() {
+            let mirrored = Square::make_square(
+                Rank::from_index(7 - square.get_rank().to_index()),
+                square.get_file(),
+            );
+
+            builder.piece(
+                mirrored,
+                board.piece_on(square).unwrap(),
+                !board.color_on(square).unwrap(),
+            );
+        }
+
+        builder
+            .side_to_move(!board.side_to_move())
+            .castle_rights(Color::White, board.castle_rights(Color::Black))
+            .castle_rights(Color::Black, board.castle_rights(Color::White))
+            .en_passant(board.en_passant().map(|square| square.get_file()));
+
+        Board::try_from(&builder).expect("mirror of a legal board is legal")
     }
 }
@@ -0,0 +1,851 @@
+use book::Book;
+use chess::{Board, ChessMove, Color, File, Rank, Square};
+use crossbeam_channel::Receiver;
+use params::EvalParams;
+use rng::{Rng, DEFAULT_SEED};
+use search::{EngineToSearch, History, Search, SearchToEngine, MAX_ELO};
+use std::{
+    str::FromStr,
+    sync::{Arc, RwLock},
+};
+use tt::{TranspositionTable, DEFAULT_HASH_SIZE};
+use uci::{EngineOption, EngineToUci, Uci, UciToEngine, DEFAULT_PV_LENGTH};
+
+pub mod bench;
+mod book;
+mod chess960;
+pub mod epd;
+mod evaluate;
+mod params;
+mod perft;
+mod rng;
+mod search;
+mod see;
+mod tt;
+pub mod tune;
+mod uci;
+
+pub use evaluate::Eval;
+pub use search::{SearchLimits, SearchResult};
+pub use uci::GameTime;
+
+pub fn think(board: Board, limits: SearchLimits) -> SearchResult {
+    let tt = TranspositionTable::new(DEFAULT_HASH_SIZE);
+
+    Search::think(board, limits, &tt)
+}
+
+pub struct Engine {
+    board: Arc<RwLock<Board>>,
+    history: Arc<RwLock<Vec<History>>>,
+    fullmove_number: u16,
+    uci: Uci,
+    search: Search,
+    quit: bool,
+    debug: bool,
+    chess960: bool,
+    chess960_position: Option<chess960::Position>,
+    book: Option<Book>,
+    book_best_move: bool,
+    rng: Rng,
+    show_wdl: bool,
+    pv_length: usize,
+    limit_strength: bool,
+    elo: u16,
+    eval_params: EvalParams,
+}
+
+impl Default for Engine {
+    fn default() -> Engine {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            board: Arc::new(RwLock::new(Board::default())),
+            history: Arc::new(RwLock::new(Vec::new())),
+            fullmove_number: 1,
+            uci: Uci::new(),
+            search: Search::new(),
+            quit: false,
+            debug: false,
+            chess960: false,
+            chess960_position: None,
+            book: None,
+            book_best_move: false,
+            rng: Rng::new(DEFAULT_SEED),
+            show_wdl: false,
+            pv_length: DEFAULT_PV_LENGTH,
+            limit_strength: false,
+            elo: MAX_ELO,
+            eval_params: EvalParams::default(),
+        }
+    }
+
+    pub fn main_loop(&mut self) {
+        let (report_tx, report_rx) = crossbeam_channel::unbounded();
+
+        self.uci.init(report_tx.clone());
+        self.search.init(
+            report_tx,
+            Arc::clone(&self.board),
+            Arc::clone(&self.history),
+        );
+
+        self.run(&report_rx);
+    }
+
+    fn run(&mut self, report_rx: &Receiver<EngineReport>) {
+        while !self.quit {
+            let Ok(report) = report_rx.recv() else {
+                self.quit();
+
+                break;
+            };
+
+            match report {
+                EngineReport::Uci(uci_report) => match uci_report {
+                    UciToEngine::Uci => self.uci.send(EngineToUci::Identify),
+                    UciToEngine::Debug(debug) => self.debug = debug,
+                    UciToEngine::IsReady => self.uci.send(EngineToUci::Ready),
+                    // registration is for copy protection, a free engine has nothing to check
+                    UciToEngine::Register => {
+                        self.debug_info(String::from("registration is not required"))
+                    }
+                    UciToEngine::Position(fen, moves) => self.set_position(&fen, moves),
+                    UciToEngine::SetOption(option) => match option {
+                        EngineOption::Hash(size) => {
+                            self.search.send(EngineToSearch::SetHashSize(size))
+                        }
+                        EngineOption::ClearHash => self.search.send(EngineToSearch::ClearHash),
+                        // the gui sends go ponder or not, there is nothing to keep on this side
+                        EngineOption::Ponder(_) => {}
+                        EngineOption::Threads(count) => {
+                            self.search.send(EngineToSearch::SetThreads(count))
+                        }
+                        EngineOption::MultiPv(lines) => {
+                            self.search.send(EngineToSearch::SetMultiPv(lines))
+                        }
+                        EngineOption::MoveOverhead(overhead) => {
+                            self.search.send(EngineToSearch::SetMoveOverhead(overhead))
+                        }
+                        EngineOption::NodesTime(nodes) => {
+                            self.search.send(EngineToSearch::SetNodesTime(nodes))
+                        }
+                        EngineOption::AspirationDelta(delta) => {
+                            self.search.send(EngineToSearch::SetAspirationDelta(delta))
+                        }
+                        EngineOption::AspirationGrowth(growth) => self
+                            .search
+                            .send(EngineToSearch::SetAspirationGrowth(growth)),
+                        EngineOption::Contempt(contempt) => {
+                            self.search.send(EngineToSearch::SetContempt(contempt))
+                        }
+                        EngineOption::EvalFile(path) => self.load_eval_params(path),
+                        EngineOption::Chess960(enabled) => self.chess960 = enabled,
+                        EngineOption::BookFile(path) => self.load_book(path),
+                        EngineOption::BookBestMove(enabled) => self.book_best_move = enabled,
+                        EngineOption::Seed(seed) => {
+                            self.rng = Rng::new(seed);
+
+                            self.search.send(EngineToSearch::SetSeed(seed))
+                        }
+                        EngineOption::ShowWdl(enabled) => self.show_wdl = enabled,
+                        EngineOption::PvLength(length) => self.pv_length = length,
+                        EngineOption::LimitStrength(enabled) => self.limit_strength = enabled,
+                        EngineOption::Elo(elo) => self.elo = elo,
+                    },
+                    UciToEngine::UciNewGame => {
+                        let board = Board::default();
+
+                        *self.board.write().unwrap() = board;
+                        *self.history.write().unwrap() = vec![History::root(&board, 0)];
+
+                        self.chess960_position = None;
+
+                        self.fullmove_number = 1;
+
+                        self.search.send(EngineToSearch::NewGame);
+                    }
+                    UciToEngine::Stop => self.search.send(EngineToSearch::Stop),
+                    UciToEngine::PonderHit => self.search.send(EngineToSearch::PonderHit),
+                    UciToEngine::Quit => self.quit(),
+                    UciToEngine::Go {
+                        mut limits,
+                        mut search_moves,
+                    } => {
+                        if self.chess960 {
+                            let board = self.board.read().unwrap();
+
+                            for m in &mut search_moves {
+                                *m = chess960::move_from_uci(&board, *m);
+                            }
+                        }
+
+                        if self.limit_strength {
+                            search::limit_strength(&mut limits, self.elo);
+                        }
+
+                        if let Some(book_move) = self.book_move(&limits, &search_moves) {
+                            let book_move = if self.chess960 {
+                                chess960::move_to_uci(&self.board.read().unwrap(), book_move)
+                            } else {
+                                book_move
+                            };
+
+                            self.debug_info(format!("book move {book_move}"));
+
+                            self.uci.send(EngineToUci::BestMove(Some(book_move)));
+
+                            continue;
+                        }
+
+                        self.debug_info(format!("search started with {limits:?}"));
+
+                        self.search
+                            .send(EngineToSearch::Start(limits, search_moves))
+                    }
+                    UciToEngine::Perft(depth) => {
+                        let divide = match &self.chess960_position {
+                            Some(position) => chess960::divide(position, depth),
+                            None => perft::divide(&self.board.read().unwrap(), depth),
+                        };
+
+                        self.uci.send(EngineToUci::Perft(divide));
+                    }
+                    UciToEngine::Eval => {
+                        let board = self.board.read().unwrap();
+
+                        let breakdown = evaluate::explain(
+                            &board,
+                            evaluate::game_phase(&board),
+                            evaluate::material(&board, &self.eval_params),
+                            &self.eval_params,
+                        );
+
+                        drop(board);
+
+                        self.uci.send(EngineToUci::Eval(breakdown));
+                    }
+                    UciToEngine::Fen => {
+                        let fen = self.current_fen();
+
+                        self.uci.send(EngineToUci::InfoString(format!("fen {fen}")));
+                    }
+                    UciToEngine::Display => {
+                        let board = *self.board.read().unwrap();
+
+                        let fen = self.current_fen();
+
+                        for line in display_lines(&board, &fen, &self.eval_params) {
+                            self.uci.send(EngineToUci::InfoString(line));
+                        }
+                    }
+                    UciToEngine::Unknown(line) => self.debug_info(format!("ignored {line}")),
+                },
+                EngineReport::Search(search_report) => match search_report {
+                    SearchToEngine::BestMove(bestmove) => {
+                        let bestmove = match bestmove {
+                            Some(bestmove) if self.chess960 => {
+                                Some(chess960::move_to_uci(&self.board.read().unwrap(), bestmove))
+                            }
+                            _ => bestmove,
+                        };
+
+                        match bestmove {
+                            Some(bestmove) => self.debug_info(format!("best move {bestmove}")),
+                            None => self.debug_info(String::from("no legal moves")),
+                        }
+
+                        self.uci.send(EngineToUci::BestMove(bestmove))
+                    }
+                    SearchToEngine::Panicked(message) => self.uci.send(EngineToUci::InfoString(
+                        format!("search panicked: {message}"),
+                    )),
+                    SearchToEngine::TimeAllocated { soft, hard } => self.debug_info(format!(
+                        "allocated {}ms soft, {}ms hard",
+                        soft.as_millis(),
+                        hard.as_millis()
+                    )),
+                    SearchToEngine::TimeUp {
+                        soft,
+                        hard,
+                        elapsed,
+                        depth,
+                    } => self.debug_info(format!(
+                        "time up after {}ms ({}ms soft, {}ms hard), completed depth {depth}",
+                        elapsed.as_millis(),
+                        soft.as_millis(),
+                        hard.as_millis()
+                    )),
+                    SearchToEngine::Researched {
+                        depth,
+                        fail_lows,
+                        fail_highs,
+                    } => self.debug_info(format!(
+                        "depth {depth} re-searched after {fail_lows} fail lows and {fail_highs} fail highs"
+                    )),
+                    SearchToEngine::CurrMove { mv, number } => {
+                        let mv = if self.chess960 {
+                            chess960::move_to_uci(&self.board.read().unwrap(), mv)
+                        } else {
+                            mv
+                        };
+
+                        self.uci.send(EngineToUci::CurrMove { mv, number })
+                    }
+                    SearchToEngine::Progress {
+                        depth,
+                        time,
+                        nodes,
+                        nps,
+                        hashfull,
+                    } => self.uci.send(EngineToUci::Progress {
+                        depth,
+                        time,
+                        nodes,
+                        nps,
+                        hashfull,
+                    }),
+                    search::SearchToEngine::Summary {
+                        depth,
+                        seldepth,
+                        multipv,
+                        time,
+                        cp,
+                        nodes,
+                        nps,
+                        hashfull,
+                        pv,
+                    } => {
+                        let mut pv = if self.chess960 {
+                            chess960::pv_to_uci(&self.board.read().unwrap(), &pv)
+                        } else {
+                            pv
+                        };
+
+                        // only the reported line is cut, zero leaves it whole
+                        if self.pv_length > 0 {
+                            pv.truncate(self.pv_length);
+                        }
+
+                        let wdl = self.show_wdl.then(|| uci::wdl(cp));
+
+                        self.uci.send(EngineToUci::Summary {
+                            depth,
+                            seldepth,
+                            multipv,
+                            time,
+                            cp,
+                            wdl,
+                            nodes,
+                            nps,
+                            hashfull,
+                            pv,
+                        })
+                    }
+                },
+            }
+        }
+    }
+
+    fn set_position(&mut self, fen: &str, moves: Vec<ChessMove>) {
+        // chess960 castling rights can name any rook, so they live beside the board
+        let parsed = if self.chess960 {
+            chess960::Position::from_fen(fen).map(|position| (position.board, Some(position)))
+        } else {
+            Board::from_str(fen).map(|new_board| (new_board, None))
+        };
+
+        let (new_board, mut position) = match parsed {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                self.debug_info(format!("invalid fen {fen}: {err}"));
+
+                return;
+            }
+        };
+
+        // the board drops the fen's move counters, but the fifty-move rule needs them
+        let halfmove_clock = fen
+            .split_whitespace()
+            .nth(4)
+            .and_then(|clock| clock.parse().ok())
+            .unwrap_or(0);
+
+        let mut fullmove_number = fen
+            .split_whitespace()
+            .nth(5)
+            .and_then(|number| number.parse().ok())
+            .unwrap_or(1);
+
+        // some guis skip ucinewgame and just send the start position again, the history is rebuilt
+        // below and killers and history scores start fresh with every search, whose own generation
+        // bump ages the old game's table
+        if new_board == Board::default() && moves.is_empty() {
+            self.debug_info(String::from("new game detected"));
+        }
+
+        let mut board = new_board;
+        let mut new_history = vec![History::root(&board, halfmove_clock)];
+
+        for m in moves {
+            let next = match &mut position {
+                Some(position) => position.make_move(m).map(|next| {
+                    *position = next;
+
+                    next.board
+                }),
+                None => board.legal(m).then(|| board.make_move_new(m)),
+            };
+
+            let Some(next) = next else {
+                self.debug_info(format!("illegal move {m} in position"));
+
+                break;
+            };
+
+            let old_pos = board;
+            board = next;
+
+            let entry = History::new(&new_history, &old_pos, &board, m);
+
+            new_history.push(entry);
+
+            if old_pos.side_to_move() == Color::Black {
+                fullmove_number += 1;
+            }
+        }
+
+        self.debug_info(format!("position set to {board}"));
+
+        *self.board.write().unwrap() = board;
+        *self.history.write().unwrap() = new_history;
+
+        self.chess960_position = position;
+
+        self.fullmove_number = fullmove_number;
+    }
+
+    fn load_book(&mut self, path: Option<String>) {
+        self.book = match path.map(|path| (Book::from_file(&path), path)) {
+            Some((Ok(book), path)) => {
+                self.debug_info(format!("loaded book from {path}"));
+
+                Some(book)
+            }
+            Some((Err(err), _)) => {
+                self.uci.send(EngineToUci::InfoString(format!(
+                    "invalid book file, playing without one: {err}"
+                )));
+
+                None
+            }
+            None => None,
+        };
+    }
+
+    // an infinite or ponder search is waiting for a stop, so it never gets an instant book reply
+    fn book_move(
+        &mut self,
+        limits: &SearchLimits,
+        search_moves: &[ChessMove],
+    ) -> Option<ChessMove> {
+        if limits.infinite || limits.ponder {
+            return None;
+        }
+
+        let book = self.book.as_ref()?;
+        let board = self.board.read().unwrap();
+
+        let book_move = match self.book_best_move {
+            true => book.best_move(&board),
+            false => book.weighted_move(&board, &mut self.rng),
+        }?;
+
+        (search_moves.is_empty() || search_moves.contains(&book_move)).then_some(book_move)
+    }
+
+    fn load_eval_params(&mut self, path: Option<String>) {
+        self.eval_params = match path.map(|path| (EvalParams::from_file(&path), path)) {
+            Some((Ok(params), path)) => {
+                self.debug_info(format!("loaded eval params from {path}"));
+
+                params
+            }
+            Some((Err(err), _)) => {
+                self.uci.send(EngineToUci::InfoString(format!(
+                    "invalid eval file, using defaults: {err}"
+                )));
+
+                EvalParams::default()
+            }
+            None => EvalParams::default(),
+        };
+
+        let params = Box::new(self.eval_params.clone());
+
+        self.search.send(EngineToSearch::SetEvalParams(params));
+    }
+
+    fn debug_info(&mut self, message: String) {
+        if self.debug {
+            self.uci.send(EngineToUci::InfoString(message));
+        }
+    }
+
+    // the chess crate prints the capturable pawn instead of the square behind it, and no counters
+    fn current_fen(&self) -> String {
+        let board = *self.board.read().unwrap();
+        let history = self.history.read().unwrap();
+
+        let placement = board.to_string();
+        let fields: Vec<&str> = placement.split_whitespace().collect();
+
+        let en_passant = board.en_passant().map_or(String::from("-"), |square| {
+            square.uforward(board.side_to_move()).to_string()
+        });
+
+        let halfmove_clock = history.last().map_or(0, |entry| entry.halfmove_clock);
+
+        format!(
+            "{} {} {} {} {} {}",
+            fields[0], fields[1], fields[2], en_passant, halfmove_clock, self.fullmove_number
+        )
+    }
+
+    fn quit(&mut self) {
+        self.search.send(EngineToSearch::Quit);
+        self.uci.quit();
+
+        self.quit = true;
+    }
+}
+
+fn display_lines(board: &Board, fen: &str, params: &EvalParams) -> Vec<String> {
+    let mut lines: Vec<String> = (0..8)
+        .rev()
+        .map(|rank| {
+            (0..8)
+                .map(|file| {
+                    let square =
+                        Square::make_square(Rank::from_index(rank), File::from_index(file));
+
+                    match (board.piece_on(square), board.color_on(square)) {
+                        (Some(piece), Some(color)) => piece.to_string(color),
+                        _ => String::from("."),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+
+    let eval = evaluate::evaluate(
+        board,
+        evaluate::game_phase(board),
+        evaluate::material(board, params),
+        params,
+    );
+
+    lines.push(format!("fen {fen}"));
+    lines.push(format!("hash {:016x}", board.get_hash()));
+    lines.push(format!("eval {eval} (side to move)"));
+    lines.push(format!("in check {}", board.checkers().popcnt() > 0));
+
+    lines
+}
+
+pub enum EngineReport {
+    Uci(UciToEngine),
+    Search(SearchToEngine),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine() -> (Engine, Receiver<EngineToUci>) {
+        let (uci, output) = Uci::capture();
+
+        let mut engine = Engine::new();
+        engine.uci = uci;
+        engine.debug = true;
+
+        (engine, output)
+    }
+
+    fn info_strings(output: &Receiver<EngineToUci>) -> Vec<String> {
+        output
+            .try_iter()
+            .filter_map(|msg| match msg {
+                EngineToUci::InfoString(text) => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn an_invalid_fen_keeps_the_previous_position() {
+        let (mut engine, output) = engine();
+
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+
+        engine.set_position(fen, Vec::new());
+        engine.set_position("4k3/8/8/8/8/8/8/4K2R x K - 0 1", Vec::new());
+        engine.set_position("not a fen", Vec::new());
+
+        assert_eq!(*engine.board.read().unwrap(), Board::from_str(fen).unwrap());
+        assert_eq!(
+            info_strings(&output)
+                .iter()
+                .filter(|text| text.starts_with("invalid fen"))
+                .count(),
+            2
+        );
+
+        // and the next good one is taken as usual
+        engine.set_position(uci::STARTPOS_FEN, Vec::new());
+
+        assert_eq!(*engine.board.read().unwrap(), Board::default());
+    }
+
+    #[test]
+    fn moves_stop_at_the_first_illegal_one() {
+        let (mut engine, output) = engine();
+
+        let moves = ["e2e4", "e7e5", "e4e5", "g1f3"].map(|m| ChessMove::from_str(m).unwrap());
+
+        engine.set_position(uci::STARTPOS_FEN, moves.to_vec());
+
+        let expected = Board::default()
+            .make_move_new(moves[0])
+            .make_move_new(moves[1]);
+
+        assert_eq!(*engine.board.read().unwrap(), expected);
+        assert_eq!(engine.history.read().unwrap().len(), 3);
+        assert!(info_strings(&output).contains(&String::from("illegal move e4e5 in position")));
+
+        // the engine still takes the next position
+        engine.set_position(uci::STARTPOS_FEN, moves[..1].to_vec());
+
+        assert_eq!(
+            *engine.board.read().unwrap(),
+            Board::default().make_move_new(moves[0])
+        );
+    }
+
+    #[test]
+    fn info_strings_are_only_sent_in_debug_mode() {
+        let (mut engine, output) = engine();
+
+        engine.debug = false;
+        engine.set_position("not a fen", Vec::new());
+        engine.set_position(uci::STARTPOS_FEN, Vec::new());
+
+        assert!(info_strings(&output).is_empty());
+
+        engine.debug = true;
+        engine.set_position("not a fen", Vec::new());
+        engine.set_position(uci::STARTPOS_FEN, Vec::new());
+
+        let info = info_strings(&output);
+
+        assert!(info[0].starts_with("invalid fen not a fen"));
+        assert!(info.contains(&String::from("new game detected")));
+        assert!(info.last().unwrap().starts_with("position set to"));
+    }
+
+    #[test]
+    fn the_engine_quits_once_nobody_can_report_to_it() {
+        let (mut engine, output) = engine();
+
+        let (report_tx, report_rx) = crossbeam_channel::unbounded();
+
+        report_tx
+            .send(EngineReport::Uci(UciToEngine::IsReady))
+            .unwrap();
+
+        drop(report_tx);
+
+        engine.run(&report_rx);
+
+        assert!(engine.quit);
+
+        let replies: Vec<_> = output.try_iter().collect();
+
+        assert!(matches!(
+            replies[..],
+            [EngineToUci::Ready, EngineToUci::Quit]
+        ));
+    }
+
+    #[test]
+    fn think_searches_a_position_without_the_uci_loop() {
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+
+        let result = think(
+            board,
+            SearchLimits {
+                max_depth: Some(4),
+                ..SearchLimits::default()
+            },
+        );
+
+        let mate = ChessMove::from_str("a1a8").unwrap();
+
+        assert_eq!(result.best_move, Some(mate));
+        assert_eq!(result.pv.first(), Some(&mate));
+        assert_eq!(result.depth, 4);
+        assert_eq!(result.score, search::INFINITY - 1);
+        assert!(result.nodes > 0);
+    }
+
+    // runs the reports through the main loop, which then quits on the closed channel
+    fn reply_to(engine: &mut Engine, reports: Vec<UciToEngine>) {
+        let (report_tx, report_rx) = crossbeam_channel::unbounded();
+
+        for report in reports {
+            report_tx.send(EngineReport::Uci(report)).unwrap();
+        }
+
+        drop(report_tx);
+
+        engine.run(&report_rx);
+    }
+
+    #[test]
+    fn display_dumps_the_position_that_was_set() {
+        let (mut engine, output) = engine();
+
+        let moves = ["e2e4", "c7c5", "g1f3"].map(|m| ChessMove::from_str(m).unwrap());
+
+        engine.set_position(uci::STARTPOS_FEN, moves.to_vec());
+        output.try_iter().for_each(drop);
+
+        reply_to(&mut engine, vec![UciToEngine::Display]);
+
+        let info = info_strings(&output);
+
+        let board = *engine.board.read().unwrap();
+
+        assert_eq!(
+            info[..8],
+            [
+                "r n b q k b n r",
+                "p p . p p p p p",
+                ". . . . . . . .",
+                ". . p . . . . .",
+                ". . . . P . . .",
+                ". . . . . N . .",
+                "P P P P . P P P",
+                "R N B Q K B . R",
+            ]
+        );
+        assert_eq!(
+            info[8],
+            "fen rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        assert_eq!(info[9], format!("hash {:016x}", board.get_hash()));
+        assert!(info[10].starts_with("eval "));
+        assert_eq!(info[11], "in check false");
+    }
+
+    #[test]
+    fn register_is_acknowledged_and_the_engine_carries_on() {
+        let (mut engine, output) = engine();
+
+        reply_to(
+            &mut engine,
+            vec![UciToEngine::Register, UciToEngine::IsReady],
+        );
+
+        let replies: Vec<_> = output.try_iter().collect();
+
+        assert!(matches!(
+            &replies[..],
+            [EngineToUci::InfoString(text), EngineToUci::Ready, EngineToUci::Quit]
+                if text == "registration is not required"
+        ));
+    }
+
+    #[test]
+    fn fen_reports_the_position_after_the_moves() {
+        // the main loop quits once it runs out of reports, so each position gets an engine
+        let fen_after = |fen: &str, moves: &[&str]| {
+            let (mut engine, output) = engine();
+
+            let moves = moves.iter().map(|m| ChessMove::from_str(m).unwrap());
+
+            engine.set_position(fen, moves.collect());
+            output.try_iter().for_each(drop);
+
+            reply_to(&mut engine, vec![UciToEngine::Fen]);
+
+            info_strings(&output).remove(0)
+        };
+
+        // the en passant square only shows right after the double push
+        assert_eq!(
+            fen_after(uci::STARTPOS_FEN, &["e2e4", "g8f6", "e4e5", "d7d5"]),
+            "fen rnbqkb1r/ppp1pppp/5n2/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3"
+        );
+
+        // the counters carry on from the ones given, and the rook move gives up castling
+        assert_eq!(
+            fen_after("4k3/8/8/8/8/8/8/4K2R w K - 10 40", &["h1h2", "e8d8"]),
+            "fen 3k4/8/8/8/8/8/7R/4K3 w - - 12 41"
+        );
+    }
+
+    #[test]
+    fn only_the_reported_pv_is_cut_to_length() {
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"].map(|m| ChessMove::from_str(m).unwrap());
+        let pv: Vec<_> = shuffle.into_iter().cycle().take(32).collect();
+
+        // zero leaves the line whole
+        for (pv_length, reported_length) in [(DEFAULT_PV_LENGTH, 20), (5, 5), (0, pv.len())] {
+            let (mut engine, output) = engine();
+
+            engine.pv_length = pv_length;
+
+            let (report_tx, report_rx) = crossbeam_channel::unbounded();
+
+            let summary = SearchToEngine::Summary {
+                depth: 30,
+                seldepth: 32,
+                multipv: 1,
+                time: chrono::Duration::milliseconds(100),
+                cp: 0,
+                nodes: 1000,
+                nps: 10000,
+                hashfull: 0,
+                pv: pv.clone(),
+            };
+
+            for report in [summary, SearchToEngine::BestMove(Some(pv[0]))] {
+                report_tx.send(EngineReport::Search(report)).unwrap();
+            }
+
+            drop(report_tx);
+
+            engine.run(&report_rx);
+
+            let replies: Vec<_> = output.try_iter().collect();
+
+            let reported = replies.iter().find_map(|reply| match reply {
+                EngineToUci::Summary { pv, .. } => Some(pv.clone()),
+                _ => None,
+            });
+            let best_move = replies.iter().find_map(|reply| match reply {
+                EngineToUci::BestMove(best_move) => *best_move,
+                _ => None,
+            });
+
+            assert_eq!(reported.as_deref(), Some(&pv[..reported_length]));
+            assert_eq!(best_move, Some(pv[0]));
+        }
+    }
+}
@@ -0,0 +1,477 @@
+use crate::evaluate::Eval;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    pub piece_values: [Eval; 6],
+    pub mobility: [Eval; 6],
+    pub piece_square_mg: [[Eval; 64]; 6],
+    pub piece_square_eg: [[Eval; 64]; 6],
+    pub passed_pawn_bonus_mg: [Eval; 8],
+    pub passed_pawn_bonus_eg: [Eval; 8],
+    pub doubled_pawn_penalty: Eval,
+    pub isolated_pawn_penalty: Eval,
+    pub backward_pawn_penalty: Eval,
+    pub bishop_pair_bonus: Eval,
+    pub rook_open_file_bonus: Eval,
+    pub rook_semi_open_file_bonus: Eval,
+    pub rook_seventh_rank_bonus: Eval,
+    pub missing_shield_pawn_penalty: Eval,
+    pub king_attacker_penalty: [Eval; 8],
+    pub tempo: Eval,
+    pub opposite_bishops_scale: Eval,
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams {
+            piece_values: PIECE_VALUES,
+            mobility: MOBILITY,
+            piece_square_mg: [
+                PAWN_TABLE_MG,
+                KNIGHT_TABLE_MG,
+                BISHOP_TABLE_MG,
+                ROOK_TABLE_MG,
+                QUEEN_TABLE_MG,
+                KING_TABLE_MG,
+            ],
+            piece_square_eg: [
+                PAWN_TABLE_EG,
+                KNIGHT_TABLE_EG,
+                BISHOP_TABLE_EG,
+                ROOK_TABLE_EG,
+                QUEEN_TABLE_EG,
+                KING_TABLE_EG,
+            ],
+            passed_pawn_bonus_mg: PASSED_PAWN_BONUS_MG,
+            passed_pawn_bonus_eg: PASSED_PAWN_BONUS_EG,
+            doubled_pawn_penalty: DOUBLED_PAWN_PENALTY,
+            isolated_pawn_penalty: ISOLATED_PAWN_PENALTY,
+            backward_pawn_penalty: BACKWARD_PAWN_PENALTY,
+            bishop_pair_bonus: BISHOP_PAIR_BONUS,
+            rook_open_file_bonus: ROOK_OPEN_FILE_BONUS,
+            rook_semi_open_file_bonus: ROOK_SEMI_OPEN_FILE_BONUS,
+            rook_seventh_rank_bonus: ROOK_SEVENTH_RANK_BONUS,
+            missing_shield_pawn_penalty: MISSING_SHIELD_PAWN_PENALTY,
+            king_attacker_penalty: KING_ATTACKER_PENALTY,
+            tempo: TEMPO,
+            opposite_bishops_scale: OPPOSITE_BISHOPS_SCALE,
+        }
+    }
+}
+
+impl EvalParams {
+    // the file is a flat toml subset: `name = value` or `name = [v, v, ...]`, arrays may span lines
+    pub fn from_file(path: &str) -> Result<EvalParams, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+
+        let mut params = EvalParams::default();
+
+        let mut statement = String::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            statement.push_str(line);
+            statement.push(' ');
+
+            if statement.matches('[').count() > statement.matches(']').count() {
+                continue;
+            }
+
+            if !statement.trim().is_empty() {
+                params
+                    .apply(&statement)
+                    .map_err(|err| format!("{path}:{}: {err}", number + 1))?;
+            }
+
+            statement.clear();
+        }
+
+        if !statement.trim().is_empty() {
+            return Err(format!("{path}: unterminated array"));
+        }
+
+        Ok(params)
+    }
+
+    fn apply(&mut self, statement: &str) -> Result<(), String> {
+        let (name, value) = statement
+            .split_once('=')
+            .ok_or_else(|| String::from("expected `name = value`"))?;
+
+        let name = name.trim();
+
+        let values = value
+            .trim()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse::<Eval>()
+                    .map_err(|_| format!("`{value}` is not a valid value for {name}"))
+            })
+            .collect::<Result<Vec<Eval>, String>>()?;
+
+        let slot = self
+            .slot(name)
+            .ok_or_else(|| format!("unknown parameter {name}"))?;
+
+        if values.len() != slot.len() {
+            return Err(format!(
+                "{name} takes {} values, got {}",
+                slot.len(),
+                values.len()
+            ));
+        }
+
+        slot.copy_from_slice(&values);
+
+        Ok(())
+    }
+
+    fn slot(&mut self, name: &str) -> Option<&mut [Eval]> {
+        let slot: &mut [Eval] = match name {
+            "piece_values" => &mut self.piece_values,
+            "mobility" => &mut self.mobility,
+            "pawn_table_mg" => &mut self.piece_square_mg[0],
+            "knight_table_mg" => &mut self.piece_square_mg[1],
+            "bishop_table_mg" => &mut self.piece_square_mg[2],
+            "rook_table_mg" => &mut self.piece_square_mg[3],
+            "queen_table_mg" => &mut self.piece_square_mg[4],
+            "king_table_mg" => &mut self.piece_square_mg[5],
+            "pawn_table_eg" => &mut self.piece_square_eg[0],
+            "knight_table_eg" => &mut self.piece_square_eg[1],
+            "bishop_table_eg" => &mut self.piece_square_eg[2],
+            "rook_table_eg" => &mut self.piece_square_eg[3],
+            "queen_table_eg" => &mut self.piece_square_eg[4],
+            "king_table_eg" => &mut self.piece_square_eg[5],
+            "passed_pawn_bonus_mg" => &mut self.passed_pawn_bonus_mg,
+            "passed_pawn_bonus_eg" => &mut self.passed_pawn_bonus_eg,
+            "doubled_pawn_penalty" => std::slice::from_mut(&mut self.doubled_pawn_penalty),
+            "isolated_pawn_penalty" => std::slice::from_mut(&mut self.isolated_pawn_penalty),
+            "backward_pawn_penalty" => std::slice::from_mut(&mut self.backward_pawn_penalty),
+            "bishop_pair_bonus" => std::slice::from_mut(&mut self.bishop_pair_bonus),
+            "rook_open_file_bonus" => std::slice::from_mut(&mut self.rook_open_file_bonus),
+            "rook_semi_open_file_bonus" => {
+                std::slice::from_mut(&mut self.rook_semi_open_file_bonus)
+            }
+            "rook_seventh_rank_bonus" => std::slice::from_mut(&mut self.rook_seventh_rank_bonus),
+            "missing_shield_pawn_penalty" => {
+                std::slice::from_mut(&mut self.missing_shield_pawn_penalty)
+            }
+            "king_attacker_penalty" => &mut self.king_attacker_penalty,
+            "tempo" => std::slice::from_mut(&mut self.tempo),
+            "opposite_bishops_scale" => std::slice::from_mut(&mut self.opposite_bishops_scale),
+            _ => return None,
+        };
+
+        Some(slot)
+    }
+}
+
+const PIECE_VALUES: [Eval; 6] = [100, 320, 330, 500, 900, 20000];
+
+const MOBILITY: [Eval; 6] = [0, 4, 5, 2, 1, 0];
+
+const MISSING_SHIELD_PAWN_PENALTY: Eval = 15;
+const KING_ATTACKER_PENALTY: [Eval; 8] = [0, 5, 20, 40, 70, 100, 130, 160];
+
+const BISHOP_PAIR_BONUS: Eval = 30;
+
+const ROOK_OPEN_FILE_BONUS: Eval = 20;
+const ROOK_SEMI_OPEN_FILE_BONUS: Eval = 10;
+const ROOK_SEVENTH_RANK_BONUS: Eval = 20;
+
+const TEMPO: Eval = 10;
+
+// a fraction of the full scale of 128, so half
+const OPPOSITE_BISHOPS_SCALE: Eval = 64;
+
+const DOUBLED_PAWN_PENALTY: Eval = 10;
+const ISOLATED_PAWN_PENALTY: Eval = 15;
+const BACKWARD_PAWN_PENALTY: Eval = 10;
+
+const PASSED_PAWN_BONUS_MG: [Eval; 8] = [0, 5, 10, 15, 25, 40, 60, 0];
+const PASSED_PAWN_BONUS_EG: [Eval; 8] = [0, 10, 20, 30, 50, 80, 120, 0];
+
+// piece-square tables read from white's side: a8..h8 first, a1..h1 last
+const PAWN_TABLE_MG: [Eval; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10, 5, 5,
+    10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 20, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5, 10, 10, -20,
+    -20, 10, 10, 5, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const PAWN_TABLE_EG: [Eval; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 60, 60, 60, 60, 60, 60, 60, 60, 40, 40, 40, 40, 40, 40, 40, 40, 20, 20,
+    20, 20, 20, 20, 20, 20, 10, 10, 10, 10, 10, 10, 10, 10, 5, 5, 5, 5, 5, 5, 5, 5, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const KNIGHT_TABLE_MG: [Eval; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, 0, 0, 0, 0, -20, -40, -30, 0, 10, 15, 15, 10,
+    0, -30, -30, 5, 15, 20, 20, 15, 5, -30, -30, 0, 15, 20, 20, 15, 0, -30, -30, 5, 10, 15, 15, 10,
+    5, -30, -40, -20, 0, 5, 5, 0, -20, -40, -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+const KNIGHT_TABLE_EG: [Eval; 64] = [
+    -40, -30, -20, -20, -20, -20, -30, -40, -30, -10, 0, 0, 0, 0, -10, -30, -20, 0, 10, 15, 15, 10,
+    0, -20, -20, 5, 15, 20, 20, 15, 5, -20, -20, 5, 15, 20, 20, 15, 5, -20, -20, 0, 10, 15, 15, 10,
+    0, -20, -30, -10, 0, 5, 5, 0, -10, -30, -40, -30, -20, -20, -20, -20, -30, -40,
+];
+
+const BISHOP_TABLE_MG: [Eval; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 10, 10, 5, 0,
+    -10, -10, 5, 5, 10, 10, 5, 5, -10, -10, 0, 10, 10, 10, 10, 0, -10, -10, 10, 10, 10, 10, 10, 10,
+    -10, -10, 5, 0, 0, 0, 0, 5, -10, -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+const BISHOP_TABLE_EG: [Eval; 64] = [
+    -15, -10, -10, -10, -10, -10, -10, -15, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0,
+    0, 0, 0, 0, 0, -10, -15, -10, -10, -10, -10, -10, -10, -15,
+];
+
+const ROOK_TABLE_MG: [Eval; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
+    0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 0, 0,
+    0, 5, 5, 0, 0, 0,
+];
+
+const ROOK_TABLE_EG: [Eval; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 5, 5, 5, 5, 5, 5, 5, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const QUEEN_TABLE_MG: [Eval; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
+    -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10, 0, 5, 0, 0,
+    0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
+];
+
+const QUEEN_TABLE_EG: [Eval; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 5, 5, 5, 5, 0, -10, -10, 5, 10, 10, 10, 10, 5,
+    -10, -5, 5, 10, 15, 15, 10, 5, -5, -5, 5, 10, 15, 15, 10, 5, -5, -10, 5, 10, 10, 10, 10, 5,
+    -10, -10, 0, 5, 5, 5, 5, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
+];
+
+const KING_TABLE_MG: [Eval; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -30, -40, -40,
+    -50, -50, -40, -40, -30, -30, -40, -40, -50, -50, -40, -40, -30, -20, -30, -30, -40, -40, -30,
+    -30, -20, -10, -20, -20, -20, -20, -20, -20, -10, 20, 20, 0, 0, 0, 0, 20, 20, 20, 30, 10, 0, 0,
+    10, 30, 20,
+];
+
+const KING_TABLE_EG: [Eval; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50, -30, -20, -10, 0, 0, -10, -20, -30, -30, -10, 20, 30,
+    30, 20, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30, -10, 30, 40, 40, 30, -10, -30, -30,
+    -10, 20, 30, 30, 20, -10, -30, -30, -30, 0, 0, 0, 0, -30, -30, -50, -30, -30, -30, -30, -30,
+    -30, -50,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evaluate::{evaluate, game_phase, material},
+        search::INFINITY,
+    };
+    use chess::Board;
+    use std::str::FromStr;
+
+    const NAMES: [&str; 27] = [
+        "piece_values",
+        "mobility",
+        "pawn_table_mg",
+        "knight_table_mg",
+        "bishop_table_mg",
+        "rook_table_mg",
+        "queen_table_mg",
+        "king_table_mg",
+        "pawn_table_eg",
+        "knight_table_eg",
+        "bishop_table_eg",
+        "rook_table_eg",
+        "queen_table_eg",
+        "king_table_eg",
+        "passed_pawn_bonus_mg",
+        "passed_pawn_bonus_eg",
+        "doubled_pawn_penalty",
+        "isolated_pawn_penalty",
+        "backward_pawn_penalty",
+        "bishop_pair_bonus",
+        "rook_open_file_bonus",
+        "rook_semi_open_file_bonus",
+        "rook_seventh_rank_bonus",
+        "missing_shield_pawn_penalty",
+        "king_attacker_penalty",
+        "tempo",
+        "opposite_bishops_scale",
+    ];
+
+    fn read_params(name: &str, text: &str) -> Result<EvalParams, String> {
+        let path =
+            std::env::temp_dir().join(format!("kittycat-{name}-{}.toml", std::process::id()));
+
+        std::fs::write(&path, text).unwrap();
+
+        let params = EvalParams::from_file(&path.to_string_lossy());
+
+        std::fs::remove_file(&path).unwrap();
+
+        params
+    }
+
+    // scalars as `name = value`, arrays eight values to a line the way the tables are laid out
+    fn to_text(params: &EvalParams) -> String {
+        let mut params = params.clone();
+
+        let mut text = String::from("# every parameter, written back out\n");
+
+        for name in NAMES {
+            let values: Vec<String> = params
+                .slot(name)
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+
+            match values.len() {
+                1 => text.push_str(&format!("{name} = {}\n", values[0])),
+                _ => {
+                    text.push_str(&format!("{name} = [\n"));
+
+                    for row in values.chunks(8) {
+                        text.push_str(&format!("    {},\n", row.join(", ")));
+                    }
+
+                    text.push_str("]\n");
+                }
+            }
+        }
+
+        text
+    }
+
+    #[test]
+    fn every_field_has_exactly_one_name() {
+        let mut params = EvalParams::default();
+        let mut next: Eval = 0;
+
+        for name in NAMES {
+            for value in params.slot(name).unwrap() {
+                *value = next;
+                next += 1;
+            }
+        }
+
+        // the struct is nothing but evals, so the names have to account for all of them
+        assert_eq!(
+            next as usize,
+            std::mem::size_of::<EvalParams>() / std::mem::size_of::<Eval>()
+        );
+
+        // two names sharing a field would leave the first one reading the second one's values
+        let mut seen: Vec<Eval> = NAMES
+            .iter()
+            .flat_map(|name| params.slot(name).unwrap().to_vec())
+            .collect();
+
+        seen.sort_unstable();
+        seen.dedup();
+
+        assert_eq!(seen.len(), next as usize);
+    }
+
+    #[test]
+    fn params_survive_a_round_trip() {
+        assert_eq!(
+            read_params("defaults", &to_text(&EvalParams::default())).unwrap(),
+            EvalParams::default()
+        );
+
+        // every value distinct and some negative, so a misplaced one can't go unnoticed
+        let mut params = EvalParams::default();
+        let mut next: Eval = -400;
+
+        for name in NAMES {
+            for value in params.slot(name).unwrap() {
+                *value = next;
+                next += 1;
+            }
+        }
+
+        assert_eq!(read_params("changed", &to_text(&params)).unwrap(), params);
+    }
+
+    #[test]
+    fn a_partial_file_keeps_the_other_defaults() {
+        let params = read_params(
+            "partial",
+            "tempo = 25 # a comment\n\ndoubled_pawn_penalty = -3\n",
+        );
+
+        let expected = EvalParams {
+            tempo: 25,
+            doubled_pawn_penalty: -3,
+            ..EvalParams::default()
+        };
+
+        assert_eq!(params.unwrap(), expected);
+    }
+
+    #[test]
+    fn bad_files_are_rejected_with_the_line() {
+        for (text, error) in [
+            (
+                "tempo = 1\nqueen_bonus = 5\n",
+                ":2: unknown parameter queen_bonus",
+            ),
+            (
+                "mobility = [1, 2, 3]\n",
+                ":1: mobility takes 6 values, got 3",
+            ),
+            ("tempo = ten\n", ":1: `ten` is not a valid value for tempo"),
+            ("tempo 10\n", ":1: expected `name = value`"),
+            ("mobility = [0, 4, 5,\n2, 1\n", ": unterminated array"),
+        ] {
+            let err = read_params("bad", text).unwrap_err();
+
+            assert!(err.ends_with(error), "{err}");
+        }
+
+        assert!(EvalParams::from_file("/nonexistent/params.toml").is_err());
+    }
+
+    #[test]
+    fn extreme_params_evaluate_without_overflow() {
+        for extreme in [Eval::MAX, Eval::MIN] {
+            let mut params = EvalParams::default();
+
+            for name in NAMES {
+                params.slot(name).unwrap().fill(extreme);
+            }
+
+            let params = read_params("extreme", &to_text(&params)).unwrap();
+
+            for fen in [
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+            ] {
+                let board = Board::from_str(fen).unwrap();
+                let eval = evaluate(
+                    &board,
+                    game_phase(&board),
+                    material(&board, &params),
+                    &params,
+                );
+
+                // clamped short of the mate scores
+                assert!(eval.abs() < INFINITY / 2, "{fen}: {eval}");
+            }
+        }
+    }
+}
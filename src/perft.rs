@@ -0,0 +1,82 @@
+use chess::{Board, ChessMove, MoveGen};
+
+pub fn perft(board: &Board, depth: u8) -> u64 {
+    let moves = MoveGen::new_legal(board);
+
+    match depth {
+        0 => 1,
+        1 => moves.len() as u64,
+        _ => moves
+            .map(|legal| perft(&board.make_move_new(legal), depth - 1))
+            .sum(),
+    }
+}
+
+pub fn divide(board: &Board, depth: u8) -> Vec<(ChessMove, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    MoveGen::new_legal(board)
+        .map(|legal| (legal, perft(&board.make_move_new(legal), depth - 1)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    // the standard perft positions, with depths kept small enough for a debug build
+    const POSITIONS: [(&str, u8, u64); 6] = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            4,
+            197281,
+        ),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            3,
+            97862,
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5, 674624),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            3,
+            9467,
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            3,
+            62379,
+        ),
+        (
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            3,
+            89890,
+        ),
+    ];
+
+    #[test]
+    fn perft_matches_the_known_counts() {
+        for (fen, depth, nodes) in POSITIONS {
+            let board = Board::from_str(fen).unwrap();
+
+            assert_eq!(perft(&board, depth), nodes, "{fen}");
+        }
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        for (fen, depth, nodes) in POSITIONS {
+            let board = Board::from_str(fen).unwrap();
+
+            let divide = divide(&board, depth);
+
+            assert_eq!(divide.len(), MoveGen::new_legal(&board).len());
+            assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), nodes);
+        }
+
+        assert!(divide(&Board::default(), 0).is_empty());
+    }
+}
@@ -1,5 +1,9 @@
 use crate::{
-    evaluate::{evaluate, Eval},
+    evaluate::{evaluate, game_phase, is_light_square, material, piece_phase, Eval},
+    params::EvalParams,
+    rng::{Rng, DEFAULT_SEED},
+    see::see,
+    tt::{Bound, TranspositionTable, DEFAULT_HASH_SIZE},
     uci::GameTime,
     EngineReport,
 };
@@ -7,7 +11,14 @@ use chess::{Board, ChessMove, Color, MoveGen, Piece, EMPTY};
 use chrono::Duration;
 use crossbeam_channel::{Receiver, Sender};
 use std::{
-    sync::{Arc, RwLock},
+    any::Any,
+    cmp::Reverse,
+    collections::VecDeque,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, RwLock,
+    },
     thread::JoinHandle,
     time::Instant,
 };
@@ -15,6 +26,67 @@ use std::{
 const MAX_PLY: u8 = 80;
 pub const INFINITY: Eval = 10000;
 
+const NULL_MOVE_REDUCTION: u8 = 2;
+
+pub const DEFAULT_ASPIRATION_DELTA: Eval = 50;
+pub const MAX_ASPIRATION_DELTA: Eval = 1000;
+pub const MAX_ASPIRATION_GROWTH: u16 = 1000;
+
+const DELTA_MARGIN: Eval = 200;
+
+const QUIESCENCE_MAX_DEPTH: u8 = 12;
+
+const FIFTY_MOVE_PLIES: u8 = 100;
+const FIFTY_MOVE_DAMPING_START: u8 = 50;
+
+const FUTILITY_MAX_DEPTH: u8 = 2;
+const FUTILITY_IMPROVING_MARGIN: Eval = 100;
+
+const SINGULAR_MIN_DEPTH: u8 = 8;
+const SINGULAR_TT_DEPTH_MARGIN: u8 = 3;
+const SINGULAR_MARGIN: Eval = 2;
+
+const IID_MIN_DEPTH: u8 = 4;
+const IID_REDUCTION: u8 = 2;
+
+const REVERSE_FUTILITY_MAX_DEPTH: u8 = 3;
+const REVERSE_FUTILITY_MARGIN: Eval = 120;
+
+pub const DEFAULT_MOVE_OVERHEAD: u64 = 30;
+pub const MIN_MOVE_OVERHEAD: u64 = 0;
+pub const MAX_MOVE_OVERHEAD: u64 = 5000;
+
+pub const MAX_CONTEMPT: Eval = 100;
+
+pub const MAX_NODES_TIME: u64 = 100_000;
+
+pub const MIN_ELO: u16 = 800;
+pub const MAX_ELO: u16 = 2400;
+
+const ELO_BASE_NODES: f64 = 32.0;
+const ELO_PER_DOUBLING: f64 = 160.0;
+const ELO_MAX_ROOT_NOISE: Eval = 200;
+
+const SOFT_LIMIT_ITERATION_FRACTION: f64 = 0.6;
+const HARD_LIMIT_FACTOR: i32 = 3;
+const HARD_LIMIT_CLOCK_DIVISOR: i32 = 4;
+const DEFAULT_MOVES_TO_GO: i32 = 30;
+const LOW_TIME_MOVES_TO_GO: i32 = 40;
+const LOW_TIME_THRESHOLD_MS: i64 = 10_000;
+const INCREMENT_USAGE_PERCENT: i32 = 75;
+const CLOCK_RESERVE_DIVISOR: i32 = 20;
+
+const CURRMOVE_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+const LMR_MIN_DEPTH: u8 = 3;
+const LMR_MIN_MOVE_INDEX: usize = 4;
+
+const PV_SCORE: i32 = 3_000_000;
+const PROMOTION_SCORE: i32 = 2_500_000;
+const CAPTURE_SCORE: i32 = 2_000_000;
+const KILLER_SCORE: i32 = 1_000_000;
+
 #[rustfmt::skip]
 const MVV_LVA: [[u8; 7]; 7] = [
     [0,  0,  0,  0,  0,  0,  0], // victim K,    attacker K, Q, R, B, N, P, None
@@ -26,21 +98,64 @@ const MVV_LVA: [[u8; 7]; 7] = [
     [0,  0,  0,  0,  0,  0,  0], // victim None, attacker K, Q, R, B, N, P, None
 ];
 
+#[derive(Debug)]
 pub enum EngineToSearch {
-    Start(SearchMode),
+    Start(SearchLimits, Vec<ChessMove>),
     Stop,
     Quit,
+    SetHashSize(usize),
+    ClearHash,
+    SetThreads(usize),
+    SetMultiPv(u16),
+    SetMoveOverhead(u64),
+    SetNodesTime(u64),
+    SetContempt(Eval),
+    SetSeed(u64),
+    SetAspirationDelta(Eval),
+    SetAspirationGrowth(u16),
+    SetEvalParams(Box<EvalParams>),
+    PonderHit,
+    NewGame,
 }
 
 pub enum SearchToEngine {
-    BestMove(ChessMove),
+    BestMove(Option<ChessMove>),
+    Panicked(String),
+    TimeAllocated {
+        soft: std::time::Duration,
+        hard: std::time::Duration,
+    },
+    TimeUp {
+        soft: std::time::Duration,
+        hard: std::time::Duration,
+        elapsed: std::time::Duration,
+        depth: u8,
+    },
+    CurrMove {
+        mv: ChessMove,
+        number: u16,
+    },
+    Researched {
+        depth: u8,
+        fail_lows: u16,
+        fail_highs: u16,
+    },
+    Progress {
+        depth: u8,
+        time: Duration,
+        nodes: u64,
+        nps: u64,
+        hashfull: u16,
+    },
     Summary {
         depth: u8,
         seldepth: u8,
+        multipv: u16,
         time: Duration,
         cp: Eval,
         nodes: u64,
         nps: u64,
+        hashfull: u16,
         pv: Vec<ChessMove>,
     },
 }
@@ -70,47 +185,105 @@ impl Search {
             let mut quit = false;
             let mut halt = true;
 
+            let mut tt = TranspositionTable::new(DEFAULT_HASH_SIZE);
+            let mut threads = 1;
+            let mut multipv = 1;
+            let mut move_overhead = DEFAULT_MOVE_OVERHEAD;
+            let mut nodes_time = 0;
+            let mut contempt = 0;
+            let mut seed = DEFAULT_SEED;
+            let mut aspiration_delta = DEFAULT_ASPIRATION_DELTA;
+            let mut aspiration_growth = 0;
+            let mut eval_params = EvalParams::default();
+
+            let mut deferred = VecDeque::new();
+
             while !quit {
-                let cmd = control_rx.recv().unwrap();
+                // settings that arrived during the last search are applied before anything newer
+                let cmd = match deferred.pop_front() {
+                    Some(cmd) => cmd,
+                    None => control_rx.recv().unwrap_or(EngineToSearch::Quit),
+                };
 
-                let mut search_mode = None;
+                let mut search_limits = None;
+                let mut search_moves = Vec::new();
 
                 match cmd {
-                    EngineToSearch::Start(sm) => {
-                        search_mode = Some(sm);
+                    EngineToSearch::Start(limits, moves) => {
+                        search_limits = Some(limits);
+                        search_moves = moves;
 
                         halt = false
                     }
                     EngineToSearch::Stop => halt = true,
                     EngineToSearch::Quit => quit = true,
+                    EngineToSearch::SetHashSize(size) => tt.resize(size),
+                    EngineToSearch::ClearHash => tt.clear(),
+                    EngineToSearch::SetThreads(count) => threads = count,
+                    EngineToSearch::SetMultiPv(lines) => multipv = lines,
+                    EngineToSearch::SetMoveOverhead(overhead) => move_overhead = overhead,
+                    EngineToSearch::SetNodesTime(nodes) => nodes_time = nodes,
+                    EngineToSearch::SetContempt(value) => contempt = value,
+                    EngineToSearch::SetSeed(value) => seed = value,
+                    EngineToSearch::SetAspirationDelta(delta) => aspiration_delta = delta,
+                    EngineToSearch::SetAspirationGrowth(growth) => aspiration_growth = growth,
+                    EngineToSearch::SetEvalParams(params) => eval_params = *params,
+                    EngineToSearch::PonderHit => {}
+                    EngineToSearch::NewGame => tt.clear(),
                 }
 
                 if !halt && !quit {
+                    let shared = SharedState::default();
+
+                    let mut search_state = SearchState::default();
+
                     let mut refs = SearchRefs {
-                        board: Arc::clone(&board),
+                        board: *board.read().unwrap(),
                         control_rx: &control_rx,
                         report_tx: &report_tx,
-                        search_mode: &search_mode.unwrap(),
-                        search_state: &mut SearchState::default(),
-                        history: Arc::clone(&history),
+                        limits: search_limits.unwrap(),
+                        search_moves: &search_moves,
+                        search_state: &mut search_state,
+                        history: history.read().unwrap().clone(),
+                        tt: &tt,
+                        multipv,
+                        move_overhead,
+                        nodes_time,
+                        contempt,
+                        // reseeded for every search, so any one search can be replayed
+                        rng: Rng::new(seed),
+                        aspiration_delta,
+                        aspiration_growth,
+                        eval_params: &eval_params,
+                        shared: &shared,
+                        thread_index: 0,
                     };
 
-                    let (best_move, terminate) = Self::iterative_deepening(&mut refs);
+                    let root = refs.board;
 
-                    let report = SearchToEngine::BestMove(best_move);
+                    let result = Self::lazy_smp(&mut refs, threads);
+
+                    let (best_move, terminate) = match result {
+                        Ok(result) => result,
+                        Err(payload) => {
+                            let report = SearchToEngine::Panicked(panic_message(payload.as_ref()));
 
-                    report_tx.send(EngineReport::Search(report)).unwrap();
-
-                    if let Some(terminate) = terminate {
-                        match terminate {
-                            SearchTerminate::Stop => {
-                                halt = true;
-                            }
-                            SearchTerminate::Quit => {
-                                halt = true;
-                                quit = true;
-                            }
+                            let _ = report_tx.send(EngineReport::Search(report));
+
+                            (MoveGen::new_legal(&root).next(), None)
                         }
+                    };
+
+                    deferred.extend(search_state.deferred.drain(..));
+
+                    let report = SearchToEngine::BestMove(best_move);
+
+                    let _ = report_tx.send(EngineReport::Search(report));
+
+                    halt = true;
+
+                    if let Some(SearchTerminate::Quit) = terminate {
+                        quit = true;
                     }
                 }
             }
@@ -122,91 +295,383 @@ impl Search {
 
     pub fn send(&self, cmd: EngineToSearch) {
         if let Some(tx) = &self.control_tx {
-            tx.send(cmd).unwrap();
+            let _ = tx.send(cmd);
         }
     }
 
-    fn iterative_deepening(refs: &mut SearchRefs) -> (ChessMove, Option<SearchTerminate>) {
-        let mut best_move = None;
-        let mut root_pv = Vec::new();
-        let mut depth = 1;
+    pub fn think(board: Board, limits: SearchLimits, tt: &TranspositionTable) -> SearchResult {
+        let (_control_tx, control_rx) = crossbeam_channel::unbounded();
+        let (report_tx, report_rx) = crossbeam_channel::unbounded();
+
+        let mut search_state = SearchState::default();
+
+        let eval_params = EvalParams::default();
+
+        let shared = SharedState::default();
+
+        let mut refs = SearchRefs {
+            board,
+            control_rx: &control_rx,
+            report_tx: &report_tx,
+            limits,
+            search_moves: &[],
+            search_state: &mut search_state,
+            history: vec![History::root(&board, 0)],
+            tt,
+            multipv: 1,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            nodes_time: 0,
+            contempt: 0,
+            rng: Rng::new(DEFAULT_SEED),
+            aspiration_delta: DEFAULT_ASPIRATION_DELTA,
+            aspiration_growth: 0,
+            eval_params: &eval_params,
+            shared: &shared,
+            thread_index: 0,
+        };
+
+        tt.new_search();
+
+        let (best_move, _) = Self::iterative_deepening(&mut refs);
+
+        let mut result = SearchResult {
+            best_move,
+            depth: 0,
+            score: 0,
+            nodes: search_state.nodes,
+            pv: Vec::new(),
+        };
+
+        for report in report_rx.try_iter() {
+            if let EngineReport::Search(SearchToEngine::Summary { depth, cp, pv, .. }) = report {
+                result.depth = depth;
+                result.score = cp;
+                result.pv = pv;
+            }
+        }
+
+        result
+    }
+
+    fn lazy_smp(
+        refs: &mut SearchRefs,
+        threads: usize,
+    ) -> std::thread::Result<(Option<ChessMove>, Option<SearchTerminate>)> {
+        // bumped once up front, so every thread stores into the same generation
+        refs.tt.new_search();
+
+        std::thread::scope(|scope| {
+            let helpers: Vec<_> = (1..threads)
+                .map(|thread_index| {
+                    let board = refs.board;
+                    let history = refs.history.clone();
+                    let search_moves = refs.search_moves;
+                    let tt = refs.tt;
+                    let move_overhead = refs.move_overhead;
+                    let contempt = refs.contempt;
+                    let aspiration_delta = refs.aspiration_delta;
+                    let aspiration_growth = refs.aspiration_growth;
+                    let eval_params = refs.eval_params;
+                    let shared = refs.shared;
+                    let rng = Rng::new(refs.rng.next_u64());
+
+                    scope.spawn(move || {
+                        // helpers never hear from the engine and nobody listens to their reports
+                        let (_control_tx, control_rx) = crossbeam_channel::unbounded();
+                        let (report_tx, _report_rx) = crossbeam_channel::unbounded();
+
+                        let mut search_state = SearchState::default();
+
+                        let mut helper = SearchRefs {
+                            board,
+                            control_rx: &control_rx,
+                            report_tx: &report_tx,
+                            limits: SearchLimits {
+                                infinite: true,
+                                ..SearchLimits::default()
+                            },
+                            search_moves,
+                            search_state: &mut search_state,
+                            history,
+                            tt,
+                            multipv: 1,
+                            move_overhead,
+                            nodes_time: 0,
+                            contempt,
+                            aspiration_delta,
+                            aspiration_growth,
+                            eval_params,
+                            shared,
+                            thread_index,
+                            rng,
+                        };
+
+                        Self::iterative_deepening(&mut helper);
+                    })
+                })
+                .collect();
+
+            let result =
+                std::panic::catch_unwind(AssertUnwindSafe(|| Self::iterative_deepening(refs)));
+
+            refs.shared.stop.store(true, Ordering::Relaxed);
+
+            // helpers only feed the shared table, the move played is the one the main thread
+            // reported, which also keeps go depth and multipv exact
+            for helper in helpers {
+                // a helper that panicked has nothing to hand back anyway
+                let _ = helper.join();
+            }
+
+            result
+        })
+    }
+
+    fn iterative_deepening(refs: &mut SearchRefs) -> (Option<ChessMove>, Option<SearchTerminate>) {
+        let mut lines: Vec<(Eval, Vec<ChessMove>)> = Vec::new();
+
+        // odd helpers start one iteration deeper so the threads don't all walk the same tree
+        let mut depth = 1 + (refs.thread_index % 2) as u8;
         let mut stop = false;
 
-        if let SearchMode::GameTime(gametime) = &refs.search_mode {
-            let is_white = refs.board.read().unwrap().side_to_move() == Color::White;
+        let mut root_moves = MoveGen::new_legal(&refs.board).collect();
+        filter_root_moves(refs, &mut root_moves);
 
-            let clock = match is_white {
-                true => gametime.white_time,
-                false => gametime.black_time,
-            };
+        let multipv = (refs.multipv as usize).min(root_moves.len());
 
-            let increment = match is_white {
-                true => gametime.white_increment,
-                false => gametime.black_increment,
-            };
+        if root_moves.is_empty() {
+            return (None, None);
+        }
 
-            let time = match gametime.moves_to_go {
-                Some(moves_to_go) => {
-                    if moves_to_go == 0 {
-                        clock
-                    } else {
-                        clock / moves_to_go as i32
-                    }
-                }
-                None => clock / 30,
-            };
+        // stop can arrive before depth 1 finishes, so start from the hash move of an earlier search
+        let mut best_move = refs
+            .tt
+            .probe(refs.board.get_hash(), 0)
+            .and_then(|entry| entry.best_move)
+            .filter(|legal| root_moves.contains(legal))
+            .or(root_moves.first().copied());
 
-            let time_slice = time + increment - Duration::milliseconds(100);
+        let is_timed = !refs.limits.ponder
+            && !refs.limits.infinite
+            && (refs.limits.game_time.is_some() || refs.limits.movetime.is_some());
 
-            refs.search_state.allocated_time = time_slice.to_std().unwrap_or_default()
+        if is_timed && root_moves.len() == 1 {
+            return (Some(root_moves[0]), None);
         }
 
+        allocate_time(refs);
+
+        // drawn once per search, so every iteration sees each root move biased the same way
+        let noise = refs.limits.root_noise;
+
+        refs.search_state.root_noise = match noise {
+            0 => Vec::new(),
+            _ => root_moves
+                .iter()
+                .map(|&legal| {
+                    let bias = refs.rng.below(2 * noise as u64 + 1) as Eval - noise;
+
+                    (legal, bias)
+                })
+                .collect(),
+        };
+
         refs.search_state.start_time = Some(Instant::now());
+        refs.search_state.phase[0] = game_phase(&refs.board);
+        refs.search_state.material[0] = material(&refs.board, refs.eval_params);
 
         while depth <= MAX_PLY && !stop {
             refs.search_state.depth = depth;
+            refs.search_state.excluded_moves.clear();
+            refs.search_state.iteration_best_move = None;
+            refs.search_state.fail_lows = 0;
+            refs.search_state.fail_highs = 0;
+
+            let mut new_lines = Vec::with_capacity(multipv);
+
+            for index in 0..multipv {
+                let (prev_eval, mut line_pv) = match lines.get(index) {
+                    Some((eval, pv)) => (Some(*eval), pv.clone()),
+                    None => (None, Vec::new()),
+                };
 
-            let eval = Self::negamax(refs, &mut root_pv, depth, -INFINITY, INFINITY);
+                let eval = Self::aspiration_search(refs, &mut line_pv, depth, prev_eval);
+
+                if refs.search_state.terminate.is_some() || line_pv.is_empty() {
+                    break;
+                }
+
+                refs.search_state.excluded_moves.push(line_pv[0]);
+
+                new_lines.push((eval, line_pv));
+            }
 
             if refs.search_state.terminate.is_none() {
-                if !root_pv.is_empty() {
-                    best_move = Some(root_pv[0]);
+                if let Some((_, pv)) = new_lines.first() {
+                    best_move = Some(pv[0]);
                 }
 
+                refs.search_state.completed_depth = depth;
+
                 let elapsed = refs.search_state.start_time.unwrap().elapsed();
 
-                let report = SearchToEngine::Summary {
-                    depth,
-                    seldepth: refs.search_state.seldepth,
-                    time: Duration::from_std(elapsed).unwrap(),
-                    cp: eval,
-                    nodes: refs.search_state.nodes,
-                    nps: (refs.search_state.nodes as f64 / elapsed.as_secs_f64()) as u64,
-                    pv: root_pv.clone(),
-                };
+                for (index, (eval, pv)) in new_lines.iter().enumerate() {
+                    let pv = extend_pv_from_tt(refs, pv, depth);
+
+                    // a panic here goes through the guard and shows up as an info string
+                    debug_assert!(
+                        is_legal_line(&refs.board, &pv),
+                        "illegal pv at depth {depth}: {}",
+                        pv.iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    );
+
+                    let report = SearchToEngine::Summary {
+                        depth,
+                        seldepth: refs.search_state.seldepth,
+                        multipv: index as u16 + 1,
+                        time: reported_time(elapsed),
+                        cp: *eval,
+                        nodes: total_nodes(refs),
+                        nps: (total_nodes(refs) as f64 / elapsed.as_secs_f64()) as u64,
+                        hashfull: refs.tt.hashfull(),
+                        pv,
+                    };
+
+                    let _ = refs.report_tx.send(EngineReport::Search(report));
+                }
+
+                let (fail_lows, fail_highs) =
+                    (refs.search_state.fail_lows, refs.search_state.fail_highs);
+
+                if fail_lows + fail_highs > 0 {
+                    let report = SearchToEngine::Researched {
+                        depth,
+                        fail_lows,
+                        fail_highs,
+                    };
+
+                    let _ = refs.report_tx.send(EngineReport::Search(report));
+                }
 
-                refs.report_tx.send(EngineReport::Search(report)).unwrap();
+                refs.search_state.last_progress = Some(Instant::now());
 
                 depth += 1;
-            }
 
-            let is_time_up = match refs.search_mode {
-                SearchMode::GameTime(_) => {
-                    refs.search_state.start_time.unwrap().elapsed()
-                        >= refs.search_state.allocated_time
+                if let (Some(mate), Some((eval, _))) = (refs.limits.mate, new_lines.first()) {
+                    if *eval >= INFINITY - 2 * mate as Eval {
+                        stop = true;
+                    }
                 }
-                _ => false,
-            };
 
-            if is_time_up || refs.search_state.terminate.is_some() {
+                lines = new_lines;
+            } else if let Some(legal) = refs.search_state.iteration_best_move {
+                // a root move that already raised alpha beats the last completed iteration's choice
+                best_move = Some(legal);
+            }
+
+            // the next iteration usually takes longer than all previous ones combined
+            let is_time_up = refs.search_state.soft_limit.is_some_and(|soft_limit| {
+                clock_elapsed(refs) >= soft_limit.mul_f64(SOFT_LIMIT_ITERATION_FRACTION)
+            });
+
+            let is_depth_reached = !refs.limits.ponder
+                && refs
+                    .limits
+                    .max_depth
+                    .is_some_and(|max_depth| depth > max_depth);
+
+            if is_time_up {
+                refs.search_state.terminate = Some(SearchTerminate::TimeUp);
+            }
+
+            if is_depth_reached || refs.search_state.terminate.is_some() {
                 stop = true;
             }
         }
 
-        (
-            best_move.unwrap_or(ChessMove::default()),
-            refs.search_state.terminate,
-        )
+        if let Some(SearchTerminate::TimeUp) = refs.search_state.terminate {
+            let hard = refs.search_state.hard_limit.unwrap_or_default();
+            let soft = refs.search_state.soft_limit.unwrap_or(hard);
+
+            let report = SearchToEngine::TimeUp {
+                soft,
+                hard,
+                elapsed: clock_elapsed(refs),
+                depth: depth - 1,
+            };
+
+            let _ = refs.report_tx.send(EngineReport::Search(report));
+        }
+
+        // the best move can't be sent until the gui tells us whether it played the pondered move
+        while refs.limits.ponder && refs.search_state.terminate.is_none() {
+            let cmd = refs.control_rx.recv().unwrap_or(EngineToSearch::Quit);
+
+            handle_command(refs, cmd);
+        }
+
+        // every make is undone before a terminated search unwinds, so the root is where we started
+        debug_assert_eq!(refs.search_state.ply, 0, "search ended away from the root");
+
+        (best_move, refs.search_state.terminate)
+    }
+
+    fn aspiration_search(
+        refs: &mut SearchRefs,
+        pv: &mut Vec<ChessMove>,
+        depth: u8,
+        prev_eval: Option<Eval>,
+    ) -> Eval {
+        let mut delta = refs.aspiration_delta;
+
+        let (mut alpha, mut beta) = match prev_eval {
+            Some(prev_eval) => (
+                (prev_eval - delta).max(-INFINITY),
+                (prev_eval + delta).min(INFINITY),
+            ),
+            None => (-INFINITY, INFINITY),
+        };
+
+        let prev_pv = pv.clone();
+
+        loop {
+            // a fail low leaves no line behind, so the re-search follows the previous one again
+            if pv.is_empty() {
+                pv.clone_from(&prev_pv);
+            }
+
+            let eval = Self::negamax(refs, pv, depth, alpha, beta, true);
+
+            if refs.search_state.terminate.is_some() {
+                return eval;
+            }
+
+            // without a growth factor the failing side opens all the way, otherwise it steps
+            // past the returned score and the step grows by that percentage each time
+            if eval <= alpha && alpha > -INFINITY {
+                refs.search_state.fail_lows += 1;
+
+                alpha = match refs.aspiration_growth {
+                    0 => -INFINITY,
+                    _ => (eval - delta).max(-INFINITY),
+                };
+            } else if eval >= beta && beta < INFINITY {
+                refs.search_state.fail_highs += 1;
+
+                beta = match refs.aspiration_growth {
+                    0 => INFINITY,
+                    _ => (eval + delta).min(INFINITY),
+                };
+            } else {
+                return eval;
+            }
+
+            delta = (delta as i32 * refs.aspiration_growth as i32 / 100).clamp(1, INFINITY as i32)
+                as Eval;
+        }
     }
 
     fn negamax(
@@ -214,9 +679,13 @@ impl Search {
         pv: &mut Vec<ChessMove>,
         mut depth: u8,
         mut alpha: Eval,
-        beta: Eval,
+        mut beta: Eval,
+        allow_null: bool,
     ) -> Eval {
-        if refs.search_state.nodes % 0x2000 == 0 {
+        // the line handed in is last iteration's continuation from here, it only guides ordering
+        let prev_pv = std::mem::take(pv);
+
+        if is_poll_due(refs) {
             check_terminate(refs);
         }
 
@@ -225,55 +694,360 @@ impl Search {
         }
 
         if refs.search_state.ply > MAX_PLY {
-            return evaluate(&refs.board.read().unwrap());
+            return static_eval(refs);
+        }
+
+        if refs.search_state.ply > 0 {
+            alpha = alpha.max(-INFINITY + refs.search_state.ply as Eval);
+            beta = beta.min(INFINITY - refs.search_state.ply as Eval);
+
+            if alpha >= beta {
+                return alpha;
+            }
         }
 
         refs.search_state.nodes += 1;
 
         let mut do_pvs = false;
 
-        let is_check = refs.board.read().unwrap().checkers() != &EMPTY;
+        let is_check = refs.board.checkers() != &EMPTY;
+
+        let ply = refs.search_state.ply as usize;
 
-        if is_check {
+        let mut extensions = match ply {
+            0 => 0,
+            _ => refs.search_state.extensions[ply - 1],
+        };
+
+        if is_check && extensions < refs.search_state.depth {
             depth += 1;
+            extensions += 1;
         }
 
+        refs.search_state.extensions[ply] = extensions;
+
         if depth == 0 {
-            return Self::quiescence(refs, pv, alpha, beta);
+            return Self::quiescence(refs, pv, alpha, beta, 0);
+        }
+
+        let hash = refs.board.get_hash();
+
+        // set while checking whether the hash move is singular, the rest of this node must not see it
+        let excluded = refs.search_state.singular_excluded[ply];
+
+        let tt_entry = refs.tt.probe(hash, refs.search_state.ply);
+
+        let mut tt_move = None;
+
+        if let Some(entry) = tt_entry {
+            tt_move = entry.best_move;
+
+            if refs.search_state.ply > 0 && entry.depth >= depth && excluded.is_none() {
+                match entry.bound {
+                    Bound::Exact => return entry.eval,
+                    Bound::Lower if entry.eval >= beta => return beta,
+                    Bound::Upper if entry.eval <= alpha => return alpha,
+                    _ => {}
+                }
+            }
+        }
+
+        let static_eval = match is_check {
+            true => None,
+            false => Some(static_eval(refs)),
+        };
+
+        refs.search_state.static_evals[ply] = static_eval;
+
+        let improving = match (static_eval, ply.checked_sub(2)) {
+            (Some(eval), Some(grandparent)) => {
+                refs.search_state.static_evals[grandparent].is_some_and(|previous| eval > previous)
+            }
+            _ => false,
+        };
+
+        if let Some(static_eval) = static_eval {
+            if refs.search_state.ply > 0
+                && excluded.is_none()
+                && depth <= REVERSE_FUTILITY_MAX_DEPTH
+                && beta.abs() < INFINITY / 2
+                && static_eval - REVERSE_FUTILITY_MARGIN * depth as Eval >= beta
+            {
+                return static_eval;
+            }
+        }
+
+        if allow_null
+            && excluded.is_none()
+            && !is_check
+            && depth >= 3
+            && refs.search_state.ply > 0
+            && has_non_pawn_material(&refs.board)
+        {
+            if let Some(old_pos) = make_null_move(refs) {
+                let mut null_pv = Vec::new();
+
+                let eval_score = -Self::negamax(
+                    refs,
+                    &mut null_pv,
+                    depth - 1 - NULL_MOVE_REDUCTION,
+                    -beta,
+                    -beta + 1,
+                    false,
+                );
+
+                unmake_move(refs, old_pos);
+
+                if refs.search_state.terminate.is_some() {
+                    return 0;
+                }
+
+                if eval_score >= beta {
+                    return beta;
+                }
+            }
+        }
+
+        let mut singular_move = None;
+
+        // if every other move falls well short of the hash score, the hash move deserves more depth
+        if let Some(entry) = tt_entry {
+            if refs.search_state.ply > 0
+                && excluded.is_none()
+                && extensions < refs.search_state.depth
+                && depth >= SINGULAR_MIN_DEPTH
+                && entry.depth + SINGULAR_TT_DEPTH_MARGIN >= depth
+                && entry.bound != Bound::Upper
+                && entry.eval.abs() < INFINITY / 2
+                && entry.best_move.is_some()
+            {
+                let singular_beta = entry.eval - SINGULAR_MARGIN * depth as Eval;
+
+                refs.search_state.singular_excluded[ply] = entry.best_move;
+
+                let eval = Self::negamax(
+                    refs,
+                    &mut Vec::new(),
+                    (depth - 1) / 2,
+                    singular_beta - 1,
+                    singular_beta,
+                    false,
+                );
+
+                refs.search_state.singular_excluded[ply] = None;
+
+                if refs.search_state.terminate.is_some() {
+                    return 0;
+                }
+
+                if eval < singular_beta {
+                    singular_move = entry.best_move;
+                }
+            }
+        }
+
+        // with nothing to try first, a shallower search of this pv node finds a move worth trying
+        if beta - alpha > 1
+            && tt_move.is_none()
+            && prev_pv.is_empty()
+            && excluded.is_none()
+            && depth >= IID_MIN_DEPTH
+        {
+            Self::negamax(
+                refs,
+                &mut Vec::new(),
+                depth - IID_REDUCTION,
+                alpha,
+                beta,
+                true,
+            );
+
+            if refs.search_state.terminate.is_some() {
+                return 0;
+            }
+
+            tt_move = refs
+                .tt
+                .probe(hash, refs.search_state.ply)
+                .and_then(|entry| entry.best_move);
         }
 
-        let ordered_moves = move_ordering(refs, pv.first().copied());
+        let mut ordered_moves = move_ordering(refs, prev_pv.first().copied().or(tt_move));
 
         let is_game_over = ordered_moves.is_empty();
 
-        for legal in ordered_moves {
+        let is_restricted =
+            refs.search_state.ply == 0 && filter_root_moves(refs, &mut ordered_moves);
+
+        let can_futility_prune = depth <= FUTILITY_MAX_DEPTH
+            && refs.search_state.ply > 0
+            && alpha.abs() < INFINITY / 2
+            && static_eval.is_some_and(|eval| {
+                eval as i32 + futility_margin(refs.eval_params, depth, improving) <= alpha as i32
+            });
+
+        let mut best_move = None;
+
+        for (move_index, legal) in ordered_moves.into_iter().enumerate() {
+            if excluded == Some(legal) {
+                continue;
+            }
+
+            let extension = (singular_move == Some(legal)) as u8;
+
+            // children read the budget spent so far from here, only the singular move spends more
+            refs.search_state.extensions[ply] = extensions + extension;
+
+            let is_capture = refs.board.piece_on(legal.get_dest()).is_some();
+
+            let is_killer =
+                refs.search_state.killers[refs.search_state.ply as usize].contains(&Some(legal));
+
+            if refs.search_state.ply == 0
+                && refs.search_state.start_time.unwrap().elapsed() >= CURRMOVE_DELAY
+            {
+                let report = SearchToEngine::CurrMove {
+                    mv: legal,
+                    number: move_index as u16 + 1,
+                };
+
+                let _ = refs.report_tx.send(EngineReport::Search(report));
+            }
+
+            // a root move's bias moves the window it is searched with, so its bounds stay exact
+            let noise = root_noise(refs, legal);
+            let (move_alpha, move_beta) = (alpha - noise, beta - noise);
+
             let old_pos = make_move(refs, legal);
 
-            let mut node_pv = Vec::new();
+            let gives_check = refs.board.checkers() != &EMPTY;
+
+            if can_futility_prune && !is_capture && !gives_check && legal.get_promotion().is_none()
+            {
+                unmake_move(refs, old_pos);
+
+                continue;
+            }
+
+            let reduction = if move_index >= LMR_MIN_MOVE_INDEX
+                && depth >= LMR_MIN_DEPTH
+                && !is_check
+                && !is_capture
+                && !is_killer
+                && !gives_check
+                && legal.get_promotion().is_none()
+            {
+                lmr_reduction(depth, move_index).saturating_sub(improving as u8)
+            } else {
+                0
+            };
+
+            // the rest of the previous line follows its own move down, so every node on it starts there
+            let mut node_pv = match prev_pv.split_first() {
+                Some((&pv_move, rest)) if pv_move == legal => rest.to_vec(),
+                _ => Vec::new(),
+            };
 
-            let mut eval_score = 0;
+            let mut eval_score = -draw_score(refs);
 
             if !is_draw(refs) {
-                if do_pvs {
-                    eval_score = -Self::negamax(refs, &mut node_pv, depth - 1, -alpha - 1, -alpha);
+                let mut full_search = true;
+
+                if reduction > 0 {
+                    eval_score = -Self::negamax(
+                        refs,
+                        &mut node_pv,
+                        depth - 1 - reduction,
+                        -move_alpha - 1,
+                        -move_alpha,
+                        true,
+                    );
+
+                    full_search = eval_score > move_alpha;
+                }
 
-                    if eval_score > alpha && eval_score < beta {
-                        eval_score = -Self::negamax(refs, &mut node_pv, depth - 1, -beta, -alpha);
+                let depth = depth + extension;
+
+                if full_search {
+                    if do_pvs {
+                        eval_score = -Self::negamax(
+                            refs,
+                            &mut node_pv,
+                            depth - 1,
+                            -move_alpha - 1,
+                            -move_alpha,
+                            true,
+                        );
+
+                        if eval_score > move_alpha && eval_score < move_beta {
+                            // a null-window search can't raise alpha without failing high, so the
+                            // re-search starts from an empty line rather than a stale one
+                            debug_assert!(node_pv.is_empty(), "null-window search left a pv");
+
+                            eval_score = -Self::negamax(
+                                refs,
+                                &mut node_pv,
+                                depth - 1,
+                                -move_beta,
+                                -move_alpha,
+                                true,
+                            );
+                        }
+                    } else {
+                        eval_score = -Self::negamax(
+                            refs,
+                            &mut node_pv,
+                            depth - 1,
+                            -move_beta,
+                            -move_alpha,
+                            true,
+                        );
                     }
-                } else {
-                    eval_score = -Self::negamax(refs, &mut node_pv, depth - 1, -beta, -alpha);
                 }
             }
 
             unmake_move(refs, old_pos);
 
+            // mate scores are left alone, a bias must not make a mate look longer or shorter
+            if eval_score.abs() < INFINITY / 2 {
+                eval_score += noise;
+            }
+
+            if refs.search_state.ply == 0
+                && eval_score > alpha
+                && refs.search_state.terminate.is_none()
+                && refs.search_state.excluded_moves.is_empty()
+            {
+                refs.search_state.iteration_best_move = Some(legal);
+            }
+
             if eval_score >= beta {
+                if !is_capture {
+                    store_killer(refs, legal);
+
+                    refs.search_state.history_heuristic[legal.get_source().to_index()]
+                        [legal.get_dest().to_index()] += depth as i32 * depth as i32;
+                }
+
+                if refs.search_state.terminate.is_none() && !is_restricted && excluded.is_none() {
+                    refs.tt.store(
+                        hash,
+                        depth,
+                        beta,
+                        Bound::Lower,
+                        Some(legal),
+                        refs.search_state.ply,
+                    );
+                }
+
                 return beta;
             }
 
             if eval_score > alpha {
                 alpha = eval_score;
 
+                best_move = Some(legal);
+
                 do_pvs = true;
 
                 pv.clear();
@@ -286,10 +1060,20 @@ impl Search {
             if is_check {
                 return -INFINITY + refs.search_state.ply as Eval;
             } else {
-                return 0;
+                return draw_score(refs);
             }
         }
 
+        if refs.search_state.terminate.is_none() && !is_restricted && excluded.is_none() {
+            let bound = match best_move {
+                Some(_) => Bound::Exact,
+                None => Bound::Upper,
+            };
+
+            refs.tt
+                .store(hash, depth, alpha, bound, best_move, refs.search_state.ply);
+        }
+
         alpha
     }
 
@@ -298,8 +1082,9 @@ impl Search {
         pv: &mut Vec<ChessMove>,
         mut alpha: Eval,
         beta: Eval,
+        depth: u8,
     ) -> Eval {
-        if refs.search_state.nodes & 0x2000 == 0 {
+        if is_poll_due(refs) {
             check_terminate(refs);
         }
 
@@ -308,33 +1093,83 @@ impl Search {
         }
 
         if refs.search_state.ply > MAX_PLY {
-            return evaluate(&refs.board.read().unwrap());
+            return static_eval(refs);
         }
 
         refs.search_state.nodes += 1;
 
         let mut do_pvs = false;
 
-        let eval = evaluate(&refs.board.read().unwrap());
+        let is_check = refs.board.checkers() != &EMPTY;
 
-        if eval >= beta {
-            return beta;
+        let mut legal_moves = MoveGen::new_legal(&refs.board);
+
+        if is_check && legal_moves.len() == 0 {
+            return -INFINITY + refs.search_state.ply as Eval;
         }
 
-        if eval > alpha {
-            alpha = eval;
+        if !is_check {
+            let board = &refs.board;
+
+            let targets = board.color_combined(!board.side_to_move());
+            legal_moves.set_iterator_mask(*targets);
+
+            // a stalemate has no captures either, so only a side without any needs the full count.
+            // it comes before the stand pat and delta pruning, which would otherwise return first
+            if legal_moves.len() == 0 && MoveGen::new_legal(&refs.board).len() == 0 {
+                return draw_score(refs);
+            }
         }
 
-        let mut legal_moves = MoveGen::new_legal(&refs.board.read().unwrap());
+        // an evasion has no stand pat, so the static eval is only needed out of check
+        let stand_pat = match is_check {
+            true => None,
+            false => Some(static_eval(refs)),
+        };
 
-        let board = refs.board.read().unwrap();
+        if let Some(eval) = stand_pat {
+            if eval >= beta {
+                return beta;
+            }
+
+            // in i32, since loaded piece values can take the sum past what an eval holds
+            let queen = refs.eval_params.piece_values[Piece::Queen.to_index()] as i32;
+
+            if eval as i32 + queen + (DELTA_MARGIN as i32) < alpha as i32 {
+                return alpha;
+            }
 
-        let targets = board.color_combined(!board.side_to_move());
-        legal_moves.set_iterator_mask(*targets);
+            if eval > alpha {
+                alpha = eval;
+            }
 
-        drop(board);
+            // long capture chains settle for the stand pat, evasions never run into the cap
+            if depth >= QUIESCENCE_MAX_DEPTH {
+                return alpha;
+            }
+        }
 
         for legal in legal_moves {
+            let board = &refs.board;
+
+            if let Some(eval) = stand_pat {
+                let piece_values = &refs.eval_params.piece_values;
+                let value = |piece: Piece| piece_values[piece.to_index()] as i32;
+
+                let captured = board.piece_on(legal.get_dest()).map_or(0, value);
+                let promotion = legal
+                    .get_promotion()
+                    .map_or(0, |piece| value(piece) - value(Piece::Pawn));
+
+                if eval as i32 + captured + promotion + (DELTA_MARGIN as i32) < alpha as i32 {
+                    continue;
+                }
+
+                if see(board, legal, piece_values) < 0 {
+                    continue;
+                }
+            }
+
             let old_pos = make_move(refs, legal);
 
             let mut node_pv = Vec::new();
@@ -342,13 +1177,15 @@ impl Search {
             let mut score;
 
             if do_pvs {
-                score = -Self::quiescence(refs, &mut node_pv, -alpha - 1, -alpha);
+                score = -Self::quiescence(refs, &mut node_pv, -alpha - 1, -alpha, depth + 1);
 
                 if score > alpha && score < beta {
-                    score = -Self::quiescence(refs, &mut node_pv, -beta, -alpha);
+                    debug_assert!(node_pv.is_empty(), "null-window search left a pv");
+
+                    score = -Self::quiescence(refs, &mut node_pv, -beta, -alpha, depth + 1);
                 }
             } else {
-                score = -Self::quiescence(refs, &mut node_pv, -beta, -alpha);
+                score = -Self::quiescence(refs, &mut node_pv, -beta, -alpha, depth + 1);
             }
 
             unmake_move(refs, old_pos);
@@ -372,45 +1209,180 @@ impl Search {
     }
 }
 
+// panic! with a literal carries a &str, with format arguments a String
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => String::from("unknown panic"),
+        },
+    }
+}
+
 fn move_ordering(refs: &mut SearchRefs, pv: Option<ChessMove>) -> Vec<ChessMove> {
-    let board = refs.board.read().unwrap();
+    let board = &refs.board;
 
-    let mut legal_moves = MoveGen::new_legal(&board);
+    let killers = refs.search_state.killers[refs.search_state.ply as usize];
 
-    let mut moves = Vec::with_capacity(legal_moves.len());
+    let mut moves: Vec<(ChessMove, i32)> = generate_moves(board)
+        .map(|(legal, kind)| match (pv, kind) {
+            (Some(pv), _) if legal == pv => (legal, PV_SCORE),
+            (_, MoveKind::QueenPromotion) => {
+                let score = MVV_LVA[piece_index(board.piece_on(legal.get_dest()))]
+                    [piece_index(Some(Piece::Pawn))];
 
-    let targets = board.color_combined(!board.side_to_move());
-    legal_moves.set_iterator_mask(*targets);
+                (legal, PROMOTION_SCORE + score as i32)
+            }
+            (_, MoveKind::Capture) => {
+                let exchange = see(board, legal, &refs.eval_params.piece_values);
 
-    for legal in &mut legal_moves {
-        moves.push(match pv {
-            Some(pv) if legal == pv => (legal, 100),
-            _ => {
-                let score = MVV_LVA[piece_index(board.piece_on(legal.get_dest()))]
-                    [piece_index(board.piece_on(legal.get_source()))];
+                if exchange < 0 {
+                    (legal, exchange)
+                } else {
+                    let score = MVV_LVA[piece_index(board.piece_on(legal.get_dest()))]
+                        [piece_index(board.piece_on(legal.get_source()))];
 
-                (legal, score)
+                    (legal, CAPTURE_SCORE + score as i32)
+                }
             }
-        });
-    }
+            (_, MoveKind::Quiet) if killers[0] == Some(legal) => (legal, KILLER_SCORE + 1),
+            (_, MoveKind::Quiet) if killers[1] == Some(legal) => (legal, KILLER_SCORE),
+            (_, MoveKind::Quiet) => {
+                let score = refs.search_state.history_heuristic[legal.get_source().to_index()]
+                    [legal.get_dest().to_index()];
 
-    legal_moves.set_iterator_mask(!EMPTY);
+                (legal, score.min(KILLER_SCORE - 1))
+            }
+        })
+        .collect();
 
-    for legal in legal_moves {
-        moves.push(match pv {
-            Some(pv) if legal == pv => (legal, 100),
-            _ => (legal, 0),
-        });
+    // the sort is stable, so shuffling first breaks ties between equal root moves by the seed
+    if refs.search_state.ply == 0 {
+        refs.rng.shuffle(&mut moves);
     }
 
-    moves.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+    moves.sort_by_key(|(_, score)| Reverse(*score));
 
     moves.into_iter().map(|(m, _)| m).collect()
 }
 
-fn piece_index(piece: Option<Piece>) -> usize {
-    match piece {
-        Some(Piece::King) => 0,
+fn generate_moves(board: &Board) -> impl Iterator<Item = (ChessMove, MoveKind)> + '_ {
+    MoveGen::new_legal(board).map(move |legal| {
+        let kind = if legal.get_promotion() == Some(Piece::Queen) {
+            MoveKind::QueenPromotion
+        } else if board.piece_on(legal.get_dest()).is_some() {
+            MoveKind::Capture
+        } else {
+            MoveKind::Quiet
+        };
+
+        (legal, kind)
+    })
+}
+
+fn is_legal_line(board: &Board, line: &[ChessMove]) -> bool {
+    let mut board = *board;
+
+    line.iter().all(|&legal| {
+        let is_legal = board.legal(legal);
+
+        if is_legal {
+            board = board.make_move_new(legal);
+        }
+
+        is_legal
+    })
+}
+
+fn extend_pv_from_tt(refs: &SearchRefs, pv: &[ChessMove], depth: u8) -> Vec<ChessMove> {
+    let mut board = refs.board;
+    let mut seen = vec![board.get_hash()];
+    let mut extended = Vec::with_capacity(pv.len().max(depth as usize));
+
+    for &legal in pv {
+        board = board.make_move_new(legal);
+
+        seen.push(board.get_hash());
+        extended.push(legal);
+    }
+
+    while extended.len() < depth as usize {
+        let Some(legal) = refs
+            .tt
+            .probe(board.get_hash(), 0)
+            .and_then(|entry| entry.best_move)
+        else {
+            break;
+        };
+
+        if !board.legal(legal) {
+            break;
+        }
+
+        board = board.make_move_new(legal);
+
+        if seen.contains(&board.get_hash()) {
+            break;
+        }
+
+        seen.push(board.get_hash());
+        extended.push(legal);
+    }
+
+    extended
+}
+
+fn filter_root_moves(refs: &SearchRefs, moves: &mut Vec<ChessMove>) -> bool {
+    let mut is_restricted = false;
+
+    if moves.iter().any(|legal| refs.search_moves.contains(legal)) {
+        moves.retain(|legal| refs.search_moves.contains(legal));
+
+        is_restricted = true;
+    }
+
+    if !refs.search_state.excluded_moves.is_empty() {
+        moves.retain(|legal| !refs.search_state.excluded_moves.contains(legal));
+
+        is_restricted = true;
+    }
+
+    is_restricted
+}
+
+fn futility_margin(params: &EvalParams, depth: u8, improving: bool) -> i32 {
+    let piece = match depth {
+        1 => Piece::Knight,
+        _ => Piece::Rook,
+    };
+
+    let margin = params.piece_values[piece.to_index()] as i32;
+
+    match improving {
+        true => margin + FUTILITY_IMPROVING_MARGIN as i32,
+        false => margin,
+    }
+}
+
+fn lmr_reduction(depth: u8, move_index: usize) -> u8 {
+    let reduction = 0.75 + (depth as f32).ln() * (move_index as f32).ln() / 2.25;
+
+    (reduction as u8).min(depth - 2)
+}
+
+fn store_killer(refs: &mut SearchRefs, legal: ChessMove) {
+    let killers = &mut refs.search_state.killers[refs.search_state.ply as usize];
+
+    if killers[0] != Some(legal) {
+        killers[1] = killers[0];
+        killers[0] = Some(legal);
+    }
+}
+
+fn piece_index(piece: Option<Piece>) -> usize {
+    match piece {
+        Some(Piece::King) => 0,
         Some(Piece::Queen) => 1,
         Some(Piece::Rook) => 2,
         Some(Piece::Bishop) => 3,
@@ -421,17 +1393,53 @@ fn piece_index(piece: Option<Piece>) -> usize {
 }
 
 fn make_move(refs: &mut SearchRefs, legal: ChessMove) -> Board {
-    let old_pos = *refs.board.read().unwrap();
+    let old_pos = refs.board;
 
-    let new_move = refs.board.read().unwrap().make_move_new(legal);
+    let new_move = refs.board.make_move_new(legal);
 
-    *refs.board.write().unwrap() = new_move;
+    let is_en_passant = old_pos.piece_on(legal.get_source()) == Some(Piece::Pawn)
+        && legal.get_source().get_file() != legal.get_dest().get_file()
+        && old_pos.piece_on(legal.get_dest()).is_none();
 
-    refs.history.write().unwrap().push(History {
-        hash: refs.board.read().unwrap().get_hash(),
-        is_reversible_move: old_pos.piece_on(legal.get_dest()).is_some()
-            || old_pos.piece_on(legal.get_source()) != Some(Piece::Pawn),
-    });
+    let captured = match is_en_passant {
+        true => Some(Piece::Pawn),
+        false => old_pos.piece_on(legal.get_dest()),
+    };
+
+    let captured_phase = captured.map_or(0, piece_phase);
+    let promotion_phase = legal.get_promotion().map_or(0, piece_phase);
+
+    let piece_values = &refs.eval_params.piece_values;
+
+    let value = |piece: Piece| piece_values[piece.to_index()] as i32;
+
+    let gained = captured.map_or(0, value)
+        + legal
+            .get_promotion()
+            .map_or(0, |piece| value(piece) - value(Piece::Pawn));
+
+    let ply = refs.search_state.ply as usize;
+
+    refs.search_state.phase[ply + 1] =
+        refs.search_state.phase[ply] - captured_phase + promotion_phase;
+
+    // material is from white's side like the rest of the eval
+    refs.search_state.material[ply + 1] = match old_pos.side_to_move() {
+        Color::White => refs.search_state.material[ply] + gained,
+        Color::Black => refs.search_state.material[ply] - gained,
+    };
+
+    debug_assert_eq!(refs.search_state.phase[ply + 1], game_phase(&new_move));
+    debug_assert_eq!(
+        refs.search_state.material[ply + 1],
+        material(&new_move, refs.eval_params)
+    );
+
+    refs.board = new_move;
+
+    let entry = History::new(&refs.history, &old_pos, &new_move, legal);
+
+    refs.history.push(entry);
 
     refs.search_state.ply += 1;
 
@@ -442,157 +1450,2341 @@ fn make_move(refs: &mut SearchRefs, legal: ChessMove) -> Board {
     old_pos
 }
 
+fn make_null_move(refs: &mut SearchRefs) -> Option<Board> {
+    let old_pos = refs.board;
+
+    let new_pos = old_pos.null_move()?;
+
+    let ply = refs.search_state.ply as usize;
+
+    refs.search_state.phase[ply + 1] = refs.search_state.phase[ply];
+    refs.search_state.material[ply + 1] = refs.search_state.material[ply];
+
+    refs.board = new_pos;
+
+    let halfmove_clock = refs.history.last().map_or(0, |entry| entry.halfmove_clock);
+
+    refs.history.push(History {
+        hash: new_pos.get_hash(),
+        halfmove_clock: halfmove_clock.saturating_add(1),
+    });
+
+    refs.search_state.ply += 1;
+
+    Some(old_pos)
+}
+
+fn current_phase(refs: &SearchRefs) -> i32 {
+    refs.search_state.phase[refs.search_state.ply as usize]
+}
+
+fn current_material(refs: &SearchRefs) -> i32 {
+    refs.search_state.material[refs.search_state.ply as usize]
+}
+
 fn unmake_move(refs: &mut SearchRefs, old_pos: Board) {
+    debug_assert!(refs.search_state.ply > 0, "unmake without a matching make");
+
     refs.search_state.ply -= 1;
 
-    *refs.board.write().unwrap() = old_pos;
+    refs.board = old_pos;
+
+    refs.history.pop();
+}
+
+fn allocate_time(refs: &mut SearchRefs) {
+    if refs.limits.ponder {
+        return;
+    }
+
+    if let Some(gametime) = &refs.limits.game_time {
+        let is_white = refs.board.side_to_move() == Color::White;
+
+        let clock = match is_white {
+            true => gametime.white_time,
+            false => gametime.black_time,
+        };
+
+        let increment = match is_white {
+            true => gametime.white_increment,
+            false => gametime.black_increment,
+        };
+
+        let overhead = Duration::milliseconds(refs.move_overhead as i64);
+
+        let (soft_limit, hard_limit) =
+            time_limits(clock, increment, gametime.moves_to_go, overhead);
+
+        refs.search_state.soft_limit = Some(soft_limit.to_std().unwrap_or_default());
+        refs.search_state.hard_limit = Some(hard_limit.to_std().unwrap_or_default());
+    }
+
+    // a movetime given alongside the clock caps whatever the clock allowed
+    if let Some(movetime) = refs.limits.movetime {
+        let movetime = movetime.to_std().unwrap_or_default();
+
+        refs.search_state.soft_limit = refs.search_state.soft_limit.map(|soft| soft.min(movetime));
+        refs.search_state.hard_limit = Some(
+            refs.search_state
+                .hard_limit
+                .map_or(movetime, |hard| hard.min(movetime)),
+        );
+    }
+
+    if let (Some(soft), Some(hard)) = (refs.search_state.soft_limit, refs.search_state.hard_limit) {
+        let report = SearchToEngine::TimeAllocated { soft, hard };
+
+        let _ = refs.report_tx.send(EngineReport::Search(report));
+    }
+}
+
+// an infinite search has to keep going until stop, however weak it's meant to be
+pub fn limit_strength(limits: &mut SearchLimits, elo: u16) {
+    if limits.infinite {
+        return;
+    }
+
+    let budget = elo_node_limit(elo);
+
+    limits.max_nodes = Some(limits.max_nodes.map_or(budget, |nodes| nodes.min(budget)));
+    limits.root_noise = elo_root_noise(elo);
+}
+
+// limited strength is a node budget per move, doubling every 160 points from 32 nodes at the
+// minimum. the curve is a rough guess rather than something calibrated against rated players
+fn elo_node_limit(elo: u16) -> u64 {
+    let doublings = (elo.clamp(MIN_ELO, MAX_ELO) - MIN_ELO) as f64 / ELO_PER_DOUBLING;
+
+    (ELO_BASE_NODES * doublings.exp2()) as u64
+}
+
+// on top of that every root move gets a random bias of up to two pawns at the minimum, shrinking
+// linearly to none at the maximum
+fn elo_root_noise(elo: u16) -> Eval {
+    let below_max = (MAX_ELO - elo.clamp(MIN_ELO, MAX_ELO)) as i32;
+
+    (ELO_MAX_ROOT_NOISE as i32 * below_max / (MAX_ELO - MIN_ELO) as i32) as Eval
+}
+
+fn root_noise(refs: &SearchRefs, legal: ChessMove) -> Eval {
+    match refs.search_state.ply {
+        0 => refs
+            .search_state
+            .root_noise
+            .iter()
+            .find(|(noisy, _)| *noisy == legal)
+            .map_or(0, |(_, noise)| *noise),
+        _ => 0,
+    }
+}
+
+fn time_limits(
+    clock: Duration,
+    increment: Duration,
+    moves_to_go: Option<u8>,
+    overhead: Duration,
+) -> (Duration, Duration) {
+    let usable = (clock - clock / CLOCK_RESERVE_DIVISOR - overhead).max(Duration::zero());
+
+    let moves = match moves_to_go {
+        Some(moves_to_go) => (moves_to_go as i32).max(1),
+        None if clock.num_milliseconds() < LOW_TIME_THRESHOLD_MS => LOW_TIME_MOVES_TO_GO,
+        None => DEFAULT_MOVES_TO_GO,
+    };
+
+    let banked_increment = increment * INCREMENT_USAGE_PERCENT / 100;
+
+    let soft_limit = (usable / moves + banked_increment).min(usable);
+    let hard_limit = (soft_limit * HARD_LIMIT_FACTOR)
+        .min(usable / HARD_LIMIT_CLOCK_DIVISOR)
+        .max(soft_limit);
 
-    refs.history.write().unwrap().pop();
+    (soft_limit, hard_limit)
+}
+
+// keeps the gui's counters moving during an iteration that takes a while to finish
+fn report_progress(refs: &mut SearchRefs) {
+    let start_time = refs.search_state.start_time.unwrap();
+
+    let last_progress = refs.search_state.last_progress.unwrap_or(start_time);
+
+    if last_progress.elapsed() < PROGRESS_INTERVAL {
+        return;
+    }
+
+    refs.search_state.last_progress = Some(Instant::now());
+
+    let elapsed = start_time.elapsed();
+
+    let report = SearchToEngine::Progress {
+        depth: refs.search_state.depth,
+        time: reported_time(elapsed),
+        nodes: total_nodes(refs),
+        nps: (total_nodes(refs) as f64 / elapsed.as_secs_f64()) as u64,
+        hashfull: refs.tt.hashfull(),
+    };
+
+    let _ = refs.report_tx.send(EngineReport::Search(report));
+}
+
+// a node limit is checked on every node so small budgets stop where they were asked to
+fn is_poll_due(refs: &SearchRefs) -> bool {
+    refs.search_state.nodes.is_multiple_of(0x2000)
+        || refs
+            .limits
+            .max_nodes
+            .is_some_and(|max_nodes| refs.search_state.nodes >= max_nodes)
 }
 
 fn check_terminate(refs: &mut SearchRefs) {
     if let Ok(cmd) = refs.control_rx.try_recv() {
-        match cmd {
-            EngineToSearch::Stop => refs.search_state.terminate = Some(SearchTerminate::Stop),
-            EngineToSearch::Quit => refs.search_state.terminate = Some(SearchTerminate::Quit),
+        handle_command(refs, cmd);
+    }
+
+    if refs.thread_index == 0 {
+        report_progress(refs);
+    }
+
+    if refs.thread_index > 0 {
+        let nodes = refs.search_state.nodes - refs.search_state.shared_nodes;
+
+        refs.shared.helper_nodes.fetch_add(nodes, Ordering::Relaxed);
+        refs.search_state.shared_nodes = refs.search_state.nodes;
+    }
+
+    if refs.shared.stop.load(Ordering::Relaxed) {
+        refs.search_state.terminate = Some(SearchTerminate::Stop);
+    }
+
+    if refs.limits.ponder {
+        return;
+    }
 
-            _ => {}
+    if let Some(hard_limit) = refs.search_state.hard_limit {
+        if clock_elapsed(refs) >= hard_limit {
+            refs.search_state.terminate = Some(SearchTerminate::TimeUp);
         }
     }
 
-    match refs.search_mode {
-        SearchMode::Infinite => {}
-        SearchMode::MoveTime(movetime) => {
-            if refs.search_state.start_time.unwrap().elapsed().as_millis()
-                > movetime.num_milliseconds() as u128
-            {
-                refs.search_state.terminate = Some(SearchTerminate::Stop);
-            }
+    if let Some(max_nodes) = refs.limits.max_nodes {
+        if total_nodes(refs) >= max_nodes {
+            refs.search_state.terminate = Some(SearchTerminate::Stop);
         }
-        SearchMode::GameTime(_) => {
-            if refs.search_state.start_time.unwrap().elapsed() >= refs.search_state.allocated_time {
-                refs.search_state.terminate = Some(SearchTerminate::Stop);
-            }
+    }
+}
+
+fn handle_command(refs: &mut SearchRefs, cmd: EngineToSearch) {
+    match cmd {
+        EngineToSearch::Stop => refs.search_state.terminate = Some(SearchTerminate::Stop),
+        EngineToSearch::Quit => refs.search_state.terminate = Some(SearchTerminate::Quit),
+        EngineToSearch::PonderHit if refs.limits.ponder => {
+            refs.limits.ponder = false;
+            refs.search_state.start_time = Some(Instant::now());
+
+            allocate_time(refs);
         }
+        EngineToSearch::PonderHit => {}
+        // a go while searching breaks the protocol, starting it later would send two best moves
+        EngineToSearch::Start(..) => {}
+        // anything else changes a setting the running search has already copied, so it waits
+        cmd => refs.search_state.deferred.push(cmd),
     }
 }
 
-fn is_draw(refs: &mut SearchRefs) -> bool {
-    is_insufficient_material(refs) || is_threefold_repetition(refs) || is_fifty_move_rule(refs)
+// with nodestime set, the clock runs at that many nodes per millisecond instead of wall time,
+// so a game replays the same however fast the machine is
+fn clock_elapsed(refs: &SearchRefs) -> std::time::Duration {
+    if refs.nodes_time > 0 && refs.limits.game_time.is_some() {
+        return std::time::Duration::from_millis(total_nodes(refs) / refs.nodes_time);
+    }
+
+    refs.search_state.start_time.unwrap().elapsed()
 }
 
-fn is_threefold_repetition(refs: &mut SearchRefs) -> bool {
-    let board = refs.board.read().unwrap();
+// std durations reach further than chrono's, anything past its range clamps instead of panicking
+fn reported_time(elapsed: std::time::Duration) -> Duration {
+    Duration::from_std(elapsed).unwrap_or(Duration::max_value())
+}
 
-    let mut count = 0;
+fn total_nodes(refs: &SearchRefs) -> u64 {
+    refs.search_state.nodes + refs.shared.helper_nodes.load(Ordering::Relaxed)
+}
 
-    for i in 0..refs.history.read().unwrap().len() {
-        if refs.history.read().unwrap()[i].hash == board.get_hash() {
-            count += 1;
-        }
+// contempt is from the root side's point of view, so it flips sign with the side to move
+fn draw_score(refs: &SearchRefs) -> Eval {
+    if refs.search_state.ply.is_multiple_of(2) {
+        -refs.contempt
+    } else {
+        refs.contempt
     }
+}
 
-    count >= 3
+fn is_draw(refs: &mut SearchRefs) -> bool {
+    is_insufficient_material(refs) || is_repetition(refs) || is_fifty_move_rule(refs)
 }
 
-fn is_fifty_move_rule(refs: &mut SearchRefs) -> bool {
-    let mut count = 0;
+fn is_repetition(refs: &mut SearchRefs) -> bool {
+    let hash = refs.board.get_hash();
 
-    for i in 0..refs.history.read().unwrap().len() {
-        if refs.history.read().unwrap()[i].is_reversible_move {
-            count += 1;
-        } else {
-            count = 0;
+    // every ply below the root pushed one entry, everything before that was played in the game
+    let game_plies = refs.history.len() - refs.search_state.ply as usize;
+
+    let mut game_count = 0;
+
+    for (index, entry) in refs.history.iter().enumerate().rev().skip(1) {
+        if entry.hash == hash {
+            // whoever steered into a repeat inside the tree can repeat it again
+            if index >= game_plies {
+                return true;
+            }
+
+            game_count += 1;
         }
 
-        if count >= 100 {
-            return true;
+        if entry.halfmove_clock == 0 {
+            break;
         }
     }
 
-    false
+    // positions from the real game need the full threefold before a draw can be claimed
+    game_count >= 2
 }
 
-fn is_insufficient_material(refs: &mut SearchRefs) -> bool {
-    let board = refs.board.read().unwrap();
+fn is_fifty_move_rule(refs: &mut SearchRefs) -> bool {
+    refs.history
+        .last()
+        .is_some_and(|entry| entry.halfmove_clock >= FIFTY_MOVE_PLIES)
+}
 
-    let white = board.color_combined(Color::White);
-    let black = board.color_combined(Color::Black);
+// past the halfway mark an advantage fades linearly to nothing at the fifty-move draw, so a win
+// gets converted while there is still time to reset the clock
+fn static_eval(refs: &SearchRefs) -> Eval {
+    let eval = evaluate(
+        &refs.board,
+        current_phase(refs),
+        current_material(refs),
+        refs.eval_params,
+    );
 
-    let white_queens = board.pieces(chess::Piece::Queen) & white;
-    let black_queens = board.pieces(chess::Piece::Queen) & black;
+    let halfmove_clock = refs.history.last().map_or(0, |entry| entry.halfmove_clock);
 
-    if white_queens.popcnt() > 0 || black_queens.popcnt() > 0 {
-        return false;
+    if halfmove_clock <= FIFTY_MOVE_DAMPING_START {
+        return eval;
     }
 
-    let white_rooks = board.pieces(chess::Piece::Rook) & white;
-    let black_rooks = board.pieces(chess::Piece::Rook) & black;
+    let remaining = FIFTY_MOVE_PLIES.saturating_sub(halfmove_clock) as i32;
 
-    if white_rooks.popcnt() > 0 || black_rooks.popcnt() > 0 {
-        return false;
-    }
+    (eval as i32 * remaining / (FIFTY_MOVE_PLIES - FIFTY_MOVE_DAMPING_START) as i32) as Eval
+}
 
-    let white_bishops = board.pieces(chess::Piece::Bishop) & white;
-    let black_bishops = board.pieces(chess::Piece::Bishop) & black;
+fn has_non_pawn_material(board: &Board) -> bool {
+    let pieces = board.pieces(Piece::Knight)
+        | board.pieces(Piece::Bishop)
+        | board.pieces(Piece::Rook)
+        | board.pieces(Piece::Queen);
 
-    if white_bishops.popcnt() > 0 && black_bishops.popcnt() > 0 {
-        return false;
-    }
+    (pieces & board.color_combined(board.side_to_move())) != EMPTY
+}
 
-    let white_knights = board.pieces(chess::Piece::Knight) & white;
-    let black_knights = board.pieces(chess::Piece::Knight) & black;
+fn is_insufficient_material(refs: &mut SearchRefs) -> bool {
+    let board = &refs.board;
+
+    let pawns_and_majors =
+        board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
 
-    if white_knights.popcnt() > 0 && black_knights.popcnt() > 0 {
+    if pawns_and_majors != EMPTY {
         return false;
     }
 
-    let white_pawns = board.pieces(chess::Piece::Pawn) & white;
-    let black_pawns = board.pieces(chess::Piece::Pawn) & black;
+    let knights = board.pieces(Piece::Knight);
+    let bishops = board.pieces(Piece::Bishop);
+
+    if (knights | bishops).popcnt() <= 1 {
+        return true;
+    }
 
-    if white_pawns.popcnt() > 0 || black_pawns.popcnt() > 0 {
+    if knights.popcnt() > 0 {
         return false;
     }
 
-    true
+    let light_bishops = bishops
+        .into_iter()
+        .filter(|&sq| is_light_square(sq))
+        .count();
+
+    light_bishops == 0 || light_bishops == bishops.popcnt() as usize
 }
 
 #[derive(Debug)]
 struct SearchRefs<'a> {
-    board: Arc<RwLock<Board>>,
+    board: Board,
     control_rx: &'a Receiver<EngineToSearch>,
     report_tx: &'a Sender<EngineReport>,
-    search_mode: &'a SearchMode,
+    limits: SearchLimits,
+    search_moves: &'a [ChessMove],
     search_state: &'a mut SearchState,
-    history: Arc<RwLock<Vec<History>>>,
+    history: Vec<History>,
+    tt: &'a TranspositionTable,
+    multipv: u16,
+    move_overhead: u64,
+    nodes_time: u64,
+    contempt: Eval,
+    rng: Rng,
+    aspiration_delta: Eval,
+    aspiration_growth: u16,
+    eval_params: &'a EvalParams,
+    shared: &'a SharedState,
+    thread_index: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
+struct SharedState {
+    stop: AtomicBool,
+    helper_nodes: AtomicU64,
+}
+
+#[derive(Clone, Debug)]
 pub struct History {
     pub hash: u64,
-    pub is_reversible_move: bool,
+    pub halfmove_clock: u8,
+}
+
+impl History {
+    pub fn root(board: &Board, halfmove_clock: u8) -> History {
+        History {
+            hash: board.get_hash(),
+            halfmove_clock,
+        }
+    }
+
+    pub fn new(history: &[History], old_pos: &Board, new_pos: &Board, m: ChessMove) -> History {
+        // a chess960 castle is sent as the king taking its own rook, which isn't a capture
+        let is_irreversible = old_pos.color_on(m.get_dest()) == Some(!old_pos.side_to_move())
+            || old_pos.piece_on(m.get_source()) == Some(Piece::Pawn);
+
+        let halfmove_clock = if is_irreversible {
+            0
+        } else {
+            history
+                .last()
+                .map_or(0, |entry| entry.halfmove_clock)
+                .saturating_add(1)
+        };
+
+        History {
+            hash: new_pos.get_hash(),
+            halfmove_clock,
+        }
+    }
 }
 
 #[derive(Debug)]
-pub enum SearchMode {
-    Infinite,
-    MoveTime(Duration),
-    GameTime(GameTime),
+pub struct SearchResult {
+    pub best_move: Option<ChessMove>,
+    pub depth: u8,
+    pub score: Eval,
+    pub nodes: u64,
+    pub pv: Vec<ChessMove>,
 }
 
 #[derive(Debug, Default)]
+pub struct SearchLimits {
+    pub max_depth: Option<u8>,
+    pub max_nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub game_time: Option<GameTime>,
+    pub mate: Option<u8>,
+    pub infinite: bool,
+    pub ponder: bool,
+    pub root_noise: Eval,
+}
+
+#[derive(Debug)]
 struct SearchState {
     nodes: u64,
     ply: u8,
     depth: u8,
     seldepth: u8,
+    completed_depth: u8,
+    shared_nodes: u64,
     terminate: Option<SearchTerminate>,
     start_time: Option<Instant>,
-    allocated_time: std::time::Duration,
+    last_progress: Option<Instant>,
+    soft_limit: Option<std::time::Duration>,
+    hard_limit: Option<std::time::Duration>,
+    killers: [[Option<ChessMove>; 2]; MAX_PLY as usize + 1],
+    phase: [i32; MAX_PLY as usize + 2],
+    material: [i32; MAX_PLY as usize + 2],
+    extensions: [u8; MAX_PLY as usize + 1],
+    static_evals: [Option<Eval>; MAX_PLY as usize + 1],
+    history_heuristic: [[i32; 64]; 64],
+    excluded_moves: Vec<ChessMove>,
+    singular_excluded: [Option<ChessMove>; MAX_PLY as usize + 1],
+    iteration_best_move: Option<ChessMove>,
+    fail_lows: u16,
+    fail_highs: u16,
+    root_noise: Vec<(ChessMove, Eval)>,
+    deferred: Vec<EngineToSearch>,
+}
+
+impl Default for SearchState {
+    fn default() -> SearchState {
+        SearchState {
+            nodes: 0,
+            ply: 0,
+            depth: 0,
+            seldepth: 0,
+            completed_depth: 0,
+            shared_nodes: 0,
+            terminate: None,
+            start_time: None,
+            last_progress: None,
+            soft_limit: None,
+            hard_limit: None,
+            killers: [[None; 2]; MAX_PLY as usize + 1],
+            phase: [0; MAX_PLY as usize + 2],
+            material: [0; MAX_PLY as usize + 2],
+            extensions: [0; MAX_PLY as usize + 1],
+            static_evals: [None; MAX_PLY as usize + 1],
+            history_heuristic: [[0; 64]; 64],
+            excluded_moves: Vec::new(),
+            singular_excluded: [None; MAX_PLY as usize + 1],
+            iteration_best_move: None,
+            fail_lows: 0,
+            fail_highs: 0,
+            root_noise: Vec::new(),
+            deferred: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MoveKind {
+    QueenPromotion,
+    Capture,
+    Quiet,
 }
 
 #[derive(Clone, Copy, Debug)]
 enum SearchTerminate {
     Stop,
+    TimeUp,
     Quit,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tt::MIN_HASH_SIZE;
+    use std::str::FromStr;
+
+    fn with_refs<T>(board: Board, test: impl FnOnce(&mut SearchRefs) -> T) -> T {
+        let (_control_tx, control_rx) = crossbeam_channel::unbounded();
+        let (report_tx, _report_rx) = crossbeam_channel::unbounded();
+
+        let mut search_state = SearchState::default();
+        let eval_params = EvalParams::default();
+        let shared = SharedState::default();
+        let tt = TranspositionTable::new(MIN_HASH_SIZE);
+
+        search_state.start_time = Some(Instant::now());
+        search_state.phase[0] = game_phase(&board);
+        search_state.material[0] = material(&board, &eval_params);
+
+        let mut refs = SearchRefs {
+            board,
+            control_rx: &control_rx,
+            report_tx: &report_tx,
+            limits: SearchLimits::default(),
+            search_moves: &[],
+            search_state: &mut search_state,
+            history: vec![History::root(&board, 0)],
+            tt: &tt,
+            multipv: 1,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            nodes_time: 0,
+            contempt: 0,
+            aspiration_delta: DEFAULT_ASPIRATION_DELTA,
+            aspiration_growth: 0,
+            eval_params: &eval_params,
+            shared: &shared,
+            thread_index: 0,
+            rng: Rng::new(DEFAULT_SEED),
+        };
+
+        test(&mut refs)
+    }
+
+    fn spawn_search() -> (Search, Receiver<EngineReport>) {
+        let board = Board::default();
+
+        let (report_tx, report_rx) = crossbeam_channel::unbounded();
+
+        let mut search = Search::new();
+        search.init(
+            report_tx,
+            Arc::new(RwLock::new(board)),
+            Arc::new(RwLock::new(vec![History::root(&board, 0)])),
+        );
+
+        (search, report_rx)
+    }
+
+    struct Reported {
+        best_move: Option<ChessMove>,
+        nodes: u64,
+        deepest: u8,
+        lines: u16,
+        pv_move: Option<ChessMove>,
+        researched: Vec<(u16, u16)>,
+    }
+
+    fn wait_for_best_move(report_rx: &Receiver<EngineReport>) -> Reported {
+        let mut reported = Reported {
+            best_move: None,
+            nodes: 0,
+            deepest: 0,
+            lines: 0,
+            pv_move: None,
+            researched: Vec::new(),
+        };
+
+        loop {
+            let report = report_rx
+                .recv_timeout(std::time::Duration::from_secs(30))
+                .expect("search never sent a best move");
+
+            match report {
+                EngineReport::Search(SearchToEngine::Summary {
+                    depth,
+                    multipv,
+                    nodes,
+                    pv,
+                    ..
+                }) => {
+                    reported.nodes = nodes;
+                    reported.deepest = reported.deepest.max(depth);
+                    reported.lines = reported.lines.max(multipv);
+
+                    if multipv == 1 {
+                        reported.pv_move = pv.first().copied();
+                    }
+                }
+                EngineReport::Search(SearchToEngine::Researched {
+                    fail_lows,
+                    fail_highs,
+                    ..
+                }) => reported.researched.push((fail_lows, fail_highs)),
+                EngineReport::Search(SearchToEngine::BestMove(best_move)) => {
+                    reported.best_move = best_move;
+
+                    return reported;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn depth(max_depth: u8) -> SearchLimits {
+        SearchLimits {
+            max_depth: Some(max_depth),
+            ..SearchLimits::default()
+        }
+    }
+
+    #[test]
+    fn same_seed_same_search() {
+        let mut runs = Vec::new();
+
+        // a new game only ages the table, so each run gets a fresh search thread
+        for _ in 0..2 {
+            let (search, report_rx) = spawn_search();
+
+            search.send(EngineToSearch::SetSeed(7));
+            search.send(EngineToSearch::Start(depth(6), Vec::new()));
+
+            let reported = wait_for_best_move(&report_rx);
+
+            runs.push((reported.best_move, reported.nodes));
+
+            search.send(EngineToSearch::Quit);
+        }
+
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[test]
+    fn clear_hash_makes_the_next_search_start_cold() {
+        let (search, report_rx) = spawn_search();
+
+        let nodes = || {
+            search.send(EngineToSearch::Start(depth(6), Vec::new()));
+
+            wait_for_best_move(&report_rx).nodes
+        };
+
+        let cold = nodes();
+        let warm = nodes();
+
+        search.send(EngineToSearch::ClearHash);
+
+        let cleared = nodes();
+
+        search.send(EngineToSearch::Quit);
+
+        assert!(warm < cold, "{warm} nodes with a warm table, {cold} cold");
+        assert_eq!(cleared, cold);
+    }
+
+    #[test]
+    fn internal_iterative_deepening_finds_a_move_to_try_first() {
+        const DEPTH: u8 = IID_MIN_DEPTH + 4;
+
+        // a free pawn is the first move ordering offers, the knight fork wins the queen
+        let board = Board::from_str("3q3k/p5pp/8/4N3/8/4B3/5PPP/6K1 w - - 0 1").unwrap();
+        let fork = ChessMove::from_str("e5f7").unwrap();
+
+        let with_iid = with_refs(board, |refs| {
+            let mut pv = Vec::new();
+
+            Search::negamax(refs, &mut pv, DEPTH, -INFINITY, INFINITY, true);
+
+            assert_eq!(pv.first(), Some(&fork));
+
+            refs.search_state.nodes
+        });
+
+        // handing in the move ordering starts with anyway searches the node as it was without iid
+        let without_iid = with_refs(board, |refs| {
+            let mut pv = vec![move_ordering(refs, None)[0]];
+
+            assert_ne!(pv[0], fork);
+
+            Search::negamax(refs, &mut pv, DEPTH, -INFINITY, INFINITY, true);
+
+            assert_eq!(pv.first(), Some(&fork));
+
+            refs.search_state.nodes
+        });
+
+        assert!(with_iid < without_iid, "{with_iid} >= {without_iid}");
+    }
+
+    #[test]
+    fn a_late_best_move_replaces_the_stale_line() {
+        let board = Board::from_str("3q3k/p5pp/8/4N3/8/4B3/5PPP/6K1 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            // last iteration's line takes the pawn, so that is searched first with the full window
+            // and the fork only wins through a null-window fail high and its re-search
+            let stale: Vec<ChessMove> = ["e3a7", "d8d1", "g1f1"]
+                .iter()
+                .map(|m| ChessMove::from_str(m).unwrap())
+                .collect();
+
+            let mut pv = stale.clone();
+
+            Search::negamax(refs, &mut pv, 5, -INFINITY, INFINITY, true);
+
+            let fork: Vec<ChessMove> = ["e5f7", "h8g8", "f7d8"]
+                .iter()
+                .map(|m| ChessMove::from_str(m).unwrap())
+                .collect();
+
+            assert_eq!(&pv[..3], &fork[..]);
+            assert!(is_legal_line(&board, &pv));
+            assert!(!pv.contains(&stale[1]));
+        });
+    }
+
+    #[test]
+    fn bad_lines_are_flagged() {
+        let line = |moves: &str| -> Vec<ChessMove> {
+            moves
+                .split_whitespace()
+                .map(|m| ChessMove::from_str(m).unwrap())
+                .collect()
+        };
+
+        let startpos = Board::default();
+
+        assert!(is_legal_line(&startpos, &[]));
+        assert!(is_legal_line(&startpos, &line("e2e4 e7e5 g1f3 b8c6")));
+
+        // the same side twice, a piece that isn't there, a blocked pawn and a move after mate
+        assert!(!is_legal_line(&startpos, &line("e2e4 d2d4")));
+        assert!(!is_legal_line(&startpos, &line("e2e4 e7e5 e2e4")));
+        assert!(!is_legal_line(&startpos, &line("e2e4 e7e5 e4e5")));
+        assert!(!is_legal_line(&startpos, &line("f2f3 e7e5 g2g4 d8h4 e1f2")));
+    }
+
+    #[test]
+    fn reported_time_saturates() {
+        let elapsed = std::time::Duration::from_millis(1234);
+
+        assert_eq!(reported_time(elapsed), Duration::milliseconds(1234));
+        assert_eq!(
+            reported_time(std::time::Duration::MAX),
+            Duration::max_value()
+        );
+    }
+
+    #[test]
+    fn time_is_split_over_the_moves_left_and_kept_inside_the_clock() {
+        let ms = Duration::milliseconds;
+
+        // clock, increment, moves to go, soft, hard, with the default overhead
+        for (clock, increment, moves_to_go, soft, hard) in [
+            // sudden death keeps a reserve and spreads the rest over thirty moves
+            (60_000, 0, None, 1899, 5697),
+            // most of the increment is banked on top
+            (60_000, 1000, None, 2649, 7947),
+            // low on time, the divisor grows
+            (5000, 0, None, 118, 354),
+            (60_000, 0, Some(10), 5697, 14_242),
+            // the last move before the control may use everything but the reserve
+            (60_000, 0, Some(1), 56_970, 56_970),
+            // an increment never lets a nearly flagged clock spend more than it has
+            (100, 2000, None, 65, 65),
+            (0, 0, None, 0, 0),
+        ] {
+            let overhead = ms(DEFAULT_MOVE_OVERHEAD as i64);
+
+            let limits = time_limits(ms(clock), ms(increment), moves_to_go, overhead);
+
+            // chrono's division can come out a few nanoseconds short
+            let off_by = |got: Duration, want: i64| (got - ms(want)).num_milliseconds();
+
+            assert_eq!(
+                (off_by(limits.0, soft), off_by(limits.1, hard)),
+                (0, 0),
+                "{clock} {increment} {moves_to_go:?}: {limits:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn move_overhead_comes_off_the_allocated_time() {
+        let soft_limits = [0, 500].map(|overhead| {
+            with_refs(Board::default(), |refs| {
+                refs.move_overhead = overhead;
+                refs.limits.game_time = Some(GameTime {
+                    white_time: Duration::seconds(10),
+                    black_time: Duration::seconds(10),
+                    white_increment: Duration::zero(),
+                    black_increment: Duration::zero(),
+                    moves_to_go: Some(1),
+                });
+
+                allocate_time(refs);
+
+                refs.search_state.soft_limit.unwrap()
+            })
+        });
+
+        assert_eq!(
+            soft_limits[0] - soft_limits[1],
+            std::time::Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn nodestime_runs_the_clock_on_nodes() {
+        const NODES_PER_MS: u64 = 1;
+
+        let runs = [0, 1].map(|_| {
+            with_refs(Board::default(), |refs| {
+                refs.nodes_time = NODES_PER_MS;
+                // on the wall clock a share of five minutes would search far more than this
+                refs.limits.game_time = Some(GameTime {
+                    white_time: Duration::minutes(5),
+                    black_time: Duration::minutes(5),
+                    white_increment: Duration::zero(),
+                    black_increment: Duration::zero(),
+                    moves_to_go: None,
+                });
+
+                Search::iterative_deepening(refs);
+
+                let budget = |limit: Option<std::time::Duration>| {
+                    limit.unwrap().as_millis() as u64 * NODES_PER_MS
+                };
+
+                let soft = budget(refs.search_state.soft_limit);
+                let hard = budget(refs.search_state.hard_limit);
+                let nodes = refs.search_state.nodes;
+
+                // the clock is only looked at every so many nodes, so the hard limit can overshoot
+                assert!(nodes as f64 >= soft as f64 * SOFT_LIMIT_ITERATION_FRACTION);
+                assert!(nodes <= hard + 0x2000, "{nodes} nodes for {hard}");
+
+                nodes
+            })
+        });
+
+        // however fast or slow the machine
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[test]
+    fn a_single_legal_move_is_played_without_searching_on_the_clock() {
+        // the king has to take the queen
+        let board = Board::from_str("k7/8/8/8/8/8/1q6/K7 w - - 0 1").unwrap();
+        let only_move = ChessMove::from_str("a1b2").unwrap();
+
+        let timed = SearchLimits {
+            movetime: Some(Duration::seconds(10)),
+            ..SearchLimits::default()
+        };
+
+        // analysis to a fixed depth still searches it for a score
+        for (limits, searched) in [(timed, false), (depth(3), true)] {
+            with_refs(board, |refs| {
+                refs.limits = limits;
+
+                let (best_move, _) = Search::iterative_deepening(refs);
+
+                assert_eq!(best_move, Some(only_move));
+                assert_eq!(refs.search_state.nodes > 0, searched);
+            });
+        }
+    }
+
+    #[test]
+    fn a_hundred_king_moves_draw_by_the_fifty_move_rule() {
+        let board = Board::from_str("4k3/p7/8/8/8/8/P7/4K3 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            // played as game moves, a search tree never gets that deep
+            let shuffle = ["e1f1", "e8f8", "f1e1", "f8e8"];
+
+            for ply in 0..FIFTY_MOVE_PLIES as usize {
+                assert!(!is_fifty_move_rule(refs), "drawn after {ply} plies");
+
+                play_in_game(refs, shuffle[ply % shuffle.len()]);
+            }
+
+            assert!(is_fifty_move_rule(refs));
+
+            // a pawn move starts the count again
+            play_in_game(refs, "a2a3");
+
+            assert!(!is_fifty_move_rule(refs));
+        });
+    }
+
+    #[test]
+    fn a_fen_halfmove_clock_counts_towards_the_fifty_move_rule() {
+        let board = Board::from_str("4k3/p7/8/8/8/8/P7/4K3 w - - 98 60").unwrap();
+
+        with_refs(board, |refs| {
+            // the board drops the fen's counters, the history root carries the clock on
+            refs.history = vec![History::root(&board, FIFTY_MOVE_PLIES - 2)];
+
+            play_in_game(refs, "e1f1");
+            assert!(!is_fifty_move_rule(refs));
+
+            play_in_game(refs, "e8f8");
+            assert!(is_fifty_move_rule(refs));
+        });
+    }
+
+    #[test]
+    fn a_win_fades_as_the_fifty_move_rule_nears() {
+        // a rook up
+        let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+
+        let static_evals = [0, FIFTY_MOVE_DAMPING_START, 75, 90, FIFTY_MOVE_PLIES].map(|clock| {
+            with_refs(board, |refs| {
+                refs.history = vec![History::root(&board, clock)];
+
+                static_eval(refs)
+            })
+        });
+
+        // untouched up to the start of the damping, then down to nothing at the draw
+        assert!(static_evals[0] > 0);
+        assert_eq!(static_evals[1], static_evals[0]);
+        assert!(static_evals[0] > static_evals[2] && static_evals[2] > static_evals[3]);
+        assert!(static_evals[3] > 0);
+        assert_eq!(static_evals[4], 0);
+    }
+
+    #[test]
+    fn low_elo_searches_less_and_sometimes_misses_the_best_move() {
+        // taking the pawn is the only move that wins anything
+        let board = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let capture = ChessMove::from_str("e4d5").unwrap();
+
+        let full_strength_nodes = with_refs(board, |refs| {
+            refs.limits = depth(8);
+
+            let (best_move, _) = Search::iterative_deepening(refs);
+
+            assert_eq!(best_move, Some(capture));
+
+            refs.search_state.nodes
+        });
+
+        let mut missed = 0;
+
+        for seed in 0..20 {
+            for noisy in [false, true] {
+                with_refs(board, |refs| {
+                    refs.rng = Rng::new(seed);
+                    limit_strength(&mut refs.limits, MIN_ELO);
+
+                    if !noisy {
+                        refs.limits.root_noise = 0;
+                    }
+
+                    let (best_move, _) = Search::iterative_deepening(refs);
+
+                    assert!(refs.search_state.nodes <= elo_node_limit(MIN_ELO));
+                    assert!(refs.search_state.nodes * 10 < full_strength_nodes);
+
+                    // the node budget alone still finds the capture, the root noise is what hides it
+                    match noisy {
+                        false => assert_eq!(best_move, Some(capture), "seed {seed}"),
+                        true => missed += (best_move != Some(capture)) as u32,
+                    }
+                });
+            }
+        }
+
+        assert!(
+            missed > 0 && missed < 20,
+            "{missed} of 20 missed the capture"
+        );
+    }
+
+    #[test]
+    fn quiescence_sees_a_stalemate_with_pieces_left() {
+        // the knight is pinned and the king is boxed in, so black is stalemated a piece down
+        let board = Board::from_str("7k/5Kn1/6P1/4B3/8/8/8/8 b - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            assert!(has_non_pawn_material(&refs.board));
+            assert!(static_eval(refs) < 0);
+
+            // with a full window and with one the stand pat would fail high on
+            for (alpha, beta) in [(-INFINITY, INFINITY), (-INFINITY, -INFINITY / 2)] {
+                let eval = Search::quiescence(refs, &mut Vec::new(), alpha, beta, 0);
+
+                assert_eq!(eval, draw_score(refs), "window {alpha} {beta}");
+            }
+        });
+    }
+
+    #[test]
+    fn quiescence_sees_a_mate() {
+        let board = Board::from_str("4R1k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let eval = Search::quiescence(refs, &mut Vec::new(), -INFINITY, INFINITY, 0);
+
+            assert_eq!(eval, -INFINITY);
+        });
+    }
+
+    #[test]
+    fn quiescence_skips_captures_that_cannot_reach_alpha() {
+        // a queen against a knight, taking the pawn wins back too little
+        let board = Board::from_str("q3k3/8/8/8/8/2p5/8/1N2K3 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let eval = Search::quiescence(refs, &mut Vec::new(), 0, 1, 0);
+
+            assert_eq!(eval, 0);
+            assert_eq!(refs.search_state.nodes, 1);
+        });
+
+        // and a node so far below alpha that even a queen would not help is cut whole
+        let board =
+            Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        with_refs(board, |refs| {
+            let eval = Search::quiescence(refs, &mut Vec::new(), 1500, 1501, 0);
+
+            assert_eq!(eval, 1500);
+            assert_eq!(refs.search_state.nodes, 1);
+        });
+    }
+
+    #[test]
+    fn quiescence_escapes_a_check_with_a_quiet_king_move() {
+        // nothing can take the rook, so a captures-only search would see no way out
+        let board = Board::from_str("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let mut pv = Vec::new();
+
+            let eval = Search::quiescence(refs, &mut pv, -INFINITY, INFINITY, 0);
+
+            assert!(eval > -INFINITY + MAX_PLY as Eval);
+            assert_eq!(pv.first().map(|m| m.get_source()), Some(chess::Square::E1));
+        });
+    }
+
+    #[test]
+    fn quiescence_stops_capturing_at_the_depth_cap() {
+        // the knight can take a loose queen
+        let board = Board::from_str("4k3/8/8/3q4/8/4N3/8/4K3 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let stand_pat = static_eval(refs);
+
+            let eval = Search::quiescence(refs, &mut Vec::new(), -INFINITY, INFINITY, 0);
+
+            assert!(eval > stand_pat);
+
+            refs.search_state.nodes = 0;
+
+            let eval = Search::quiescence(
+                refs,
+                &mut Vec::new(),
+                -INFINITY,
+                INFINITY,
+                QUIESCENCE_MAX_DEPTH,
+            );
+
+            assert_eq!(eval, stand_pat);
+            assert_eq!(refs.search_state.nodes, 1);
+        });
+
+        // an evasion is searched past the cap all the same
+        let board = Board::from_str("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let mut pv = Vec::new();
+
+            let eval = Search::quiescence(refs, &mut pv, -INFINITY, INFINITY, QUIESCENCE_MAX_DEPTH);
+
+            assert!(eval > -INFINITY + MAX_PLY as Eval);
+            assert_eq!(pv.first().map(|m| m.get_source()), Some(chess::Square::E1));
+        });
+    }
+
+    #[test]
+    fn the_previous_pv_is_followed_below_the_root() {
+        const DEPTH: u8 = 6;
+
+        let board =
+            Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        let previous = with_refs(board, |refs| {
+            let mut pv = Vec::new();
+
+            Search::negamax(refs, &mut pv, DEPTH - 1, -INFINITY, INFINITY, true);
+
+            pv
+        });
+
+        // each on a fresh table, so the handed in line is all there is to go on
+        let nodes = |line: &[ChessMove]| {
+            with_refs(board, |refs| {
+                let mut pv = line.to_vec();
+
+                Search::negamax(refs, &mut pv, DEPTH, -INFINITY, INFINITY, true);
+
+                refs.search_state.nodes
+            })
+        };
+
+        let cold = nodes(&[]);
+        let root_only = nodes(&previous[..1]);
+        let full = nodes(&previous);
+
+        assert!(previous.len() > 1);
+        assert!(
+            full < root_only && full < cold,
+            "{full} nodes following the whole line, {root_only} its first move, {cold} cold"
+        );
+    }
+
+    #[test]
+    fn a_terminated_search_unwinds_to_the_root() {
+        // kiwipete, busy enough that every limit stops somewhere in the middle of the tree
+        let board =
+            Board::from_str("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        for max_nodes in [1, 10, 100, 1000, 12345] {
+            with_refs(board, |refs| {
+                refs.limits.max_nodes = Some(max_nodes);
+
+                let (best_move, terminate) = Search::iterative_deepening(refs);
+
+                assert!(terminate.is_some(), "{max_nodes} nodes finished the search");
+                assert!(best_move.is_some_and(|legal| board.legal(legal)));
+
+                assert_eq!(refs.search_state.ply, 0);
+                assert_eq!(refs.board, board);
+                assert_eq!(refs.history.len(), 1);
+            });
+        }
+    }
+
+    #[test]
+    fn a_stop_before_any_root_move_is_scored_plays_the_hash_move() {
+        let board = Board::default();
+        let first_legal = MoveGen::new_legal(&board).next();
+
+        with_refs(board, |refs| {
+            refs.limits.max_nodes = Some(1);
+
+            assert_eq!(Search::iterative_deepening(refs).0, first_legal);
+
+            *refs.search_state = SearchState::default();
+            refs.limits = depth(6);
+
+            let (searched, _) = Search::iterative_deepening(refs);
+
+            assert!(searched.is_some());
+            assert_ne!(searched, first_legal);
+
+            // the same table, but stopped straight away
+            *refs.search_state = SearchState::default();
+            refs.limits = SearchLimits {
+                max_nodes: Some(1),
+                ..SearchLimits::default()
+            };
+
+            let (best_move, terminate) = Search::iterative_deepening(refs);
+
+            assert!(terminate.is_some());
+            assert_eq!(refs.search_state.completed_depth, 0);
+            assert_eq!(best_move, searched);
+        });
+    }
+
+    #[test]
+    fn go_nodes_searches_exactly_the_budget() {
+        for max_nodes in [1, 500, 20_000] {
+            with_refs(Board::default(), |refs| {
+                refs.limits.max_nodes = Some(max_nodes);
+
+                Search::iterative_deepening(refs);
+
+                assert_eq!(refs.search_state.nodes, max_nodes);
+            });
+        }
+    }
+
+    #[test]
+    fn combined_limits_stop_at_whichever_comes_first() {
+        let move_time = |ms| Some(Duration::milliseconds(ms));
+
+        // limits, completed depth if that limit decides, nodes if those do
+        for (limits, completed_depth, nodes) in [
+            (
+                SearchLimits {
+                    max_depth: Some(3),
+                    max_nodes: Some(1_000_000),
+                    ..SearchLimits::default()
+                },
+                Some(3),
+                None,
+            ),
+            (
+                SearchLimits {
+                    max_depth: Some(30),
+                    max_nodes: Some(5000),
+                    ..SearchLimits::default()
+                },
+                None,
+                Some(5000),
+            ),
+            (
+                SearchLimits {
+                    max_depth: Some(2),
+                    movetime: move_time(10_000),
+                    ..SearchLimits::default()
+                },
+                Some(2),
+                None,
+            ),
+            (
+                SearchLimits {
+                    max_nodes: Some(5000),
+                    movetime: move_time(10_000),
+                    ..SearchLimits::default()
+                },
+                None,
+                Some(5000),
+            ),
+        ] {
+            with_refs(Board::default(), |refs| {
+                refs.limits = limits;
+
+                let start = Instant::now();
+
+                let (best_move, _) = Search::iterative_deepening(refs);
+
+                assert!(best_move.is_some());
+                assert!(start.elapsed() < std::time::Duration::from_secs(5));
+
+                if let Some(completed_depth) = completed_depth {
+                    assert_eq!(refs.search_state.completed_depth, completed_depth);
+                }
+
+                if let Some(nodes) = nodes {
+                    assert_eq!(refs.search_state.nodes, nodes);
+                }
+            });
+        }
+
+        // and a short movetime cuts off a deep depth limit
+        with_refs(Board::default(), |refs| {
+            refs.limits = SearchLimits {
+                max_depth: Some(30),
+                movetime: move_time(100),
+                ..SearchLimits::default()
+            };
+
+            Search::iterative_deepening(refs);
+
+            assert!(refs.search_state.completed_depth < 30);
+            assert!(clock_elapsed(refs) < std::time::Duration::from_secs(1));
+        });
+    }
+
+    #[test]
+    fn go_mate_stops_once_the_mate_is_found() {
+        // the doubled rooks mate on the back rank in two, going through the black rook
+        let board = Board::from_str("3r2k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            refs.limits.mate = Some(2);
+
+            let (best_move, terminate) = Search::iterative_deepening(refs);
+
+            assert_eq!(best_move, Some(ChessMove::from_str("e2e8").unwrap()));
+            // nothing else would have stopped a search without limits before MAX_PLY
+            assert!(terminate.is_none());
+            assert!(refs.search_state.completed_depth <= 4);
+        });
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "unmake without a matching make")]
+    fn unmake_without_make_is_caught() {
+        with_refs(Board::default(), |refs| unmake_move(refs, Board::default()));
+    }
+
+    #[test]
+    fn incremental_material_and_phase_match_a_recount() {
+        let mut rng = Rng::new(DEFAULT_SEED);
+
+        // a promotion race and an en passant setup on top of the start position
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "8/1P4k1/8/8/8/8/1K4p1/8 w - - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        ] {
+            with_refs(Board::from_str(fen).unwrap(), |refs| {
+                let mut undo = Vec::new();
+
+                while refs.search_state.ply < MAX_PLY {
+                    let moves: Vec<_> = MoveGen::new_legal(&refs.board).collect();
+
+                    if moves.is_empty() {
+                        break;
+                    }
+
+                    let legal = moves[rng.below(moves.len() as u64) as usize];
+
+                    undo.push(make_move(refs, legal));
+
+                    assert_eq!(current_phase(refs), game_phase(&refs.board));
+                    assert_eq!(
+                        current_material(refs),
+                        material(&refs.board, refs.eval_params)
+                    );
+                }
+
+                while let Some(old_pos) = undo.pop() {
+                    unmake_move(refs, old_pos);
+                }
+
+                assert_eq!(
+                    current_material(refs),
+                    material(&refs.board, refs.eval_params)
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn the_cached_phase_follows_the_ply() {
+        let board = Board::from_str("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2")
+            .unwrap();
+
+        with_refs(board, |refs| {
+            let start_phase = current_phase(refs);
+            let start_material = current_material(refs);
+
+            let old_pos = make_null_move(refs).unwrap();
+
+            assert_eq!(current_phase(refs), start_phase);
+            assert_eq!(current_material(refs), start_material);
+
+            unmake_move(refs, old_pos);
+
+            // a pawn capture leaves the phase alone, a queen trade does not
+            let mut undo = vec![make_move(refs, ChessMove::from_str("e4d5").unwrap())];
+
+            assert_eq!(current_phase(refs), start_phase);
+            assert!(current_material(refs) > start_material);
+
+            for m in ["d8d5", "b1c3", "d5d2", "c1d2"] {
+                undo.push(make_move(refs, ChessMove::from_str(m).unwrap()));
+            }
+
+            assert!(current_phase(refs) < start_phase);
+            assert_eq!(current_phase(refs), game_phase(&refs.board));
+
+            while let Some(old_pos) = undo.pop() {
+                unmake_move(refs, old_pos);
+            }
+
+            assert_eq!(current_phase(refs), start_phase);
+            assert_eq!(current_material(refs), start_material);
+        });
+    }
+
+    #[test]
+    fn promotions_come_first_and_losing_captures_last() {
+        // the knight hangs, the pawn on e5 is defended
+        let board = Board::from_str("4k3/1P6/3p4/n3p3/8/2Q5/8/4K3 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let [pv, promotion, good, bad] =
+                ["e1d1", "b7b8q", "c3a5", "c3e5"].map(|m| ChessMove::from_str(m).unwrap());
+
+            let moves = move_ordering(refs, Some(pv));
+
+            assert_eq!(&moves[..3], &[pv, promotion, good]);
+            assert_eq!(moves.last(), Some(&bad));
+
+            // every legal move is ordered exactly once
+            let mut sorted = moves.clone();
+            sorted.sort_by_key(|m| m.to_string());
+            sorted.dedup();
+
+            assert_eq!(sorted.len(), moves.len());
+            assert_eq!(moves.len(), MoveGen::new_legal(&refs.board).len());
+        });
+    }
+
+    #[test]
+    fn killers_come_after_captures_and_before_other_quiets() {
+        let board = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let [capture, older, newer] =
+                ["e4d5", "e1f1", "e1d1"].map(|m| ChessMove::from_str(m).unwrap());
+
+            store_killer(refs, older);
+            store_killer(refs, newer);
+
+            // a repeated cutoff doesn't push the older killer out
+            store_killer(refs, newer);
+
+            assert_eq!(refs.search_state.killers[0], [Some(newer), Some(older)]);
+            assert_eq!(&move_ordering(refs, None)[..3], &[capture, newer, older]);
+        });
+    }
+
+    #[test]
+    fn history_orders_the_remaining_quiets_below_the_killers() {
+        let board = Board::from_str("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let [capture, killer, push, better, worse] =
+                ["e4d5", "e1d1", "e4e5", "e1e2", "e1f2"].map(|m| ChessMove::from_str(m).unwrap());
+
+            let mut score = |legal: ChessMove, value: i32| {
+                refs.search_state.history_heuristic[legal.get_source().to_index()]
+                    [legal.get_dest().to_index()] = value;
+            };
+
+            // a history score however large never climbs past a killer
+            score(push, i32::MAX);
+            score(better, 50);
+            score(worse, 10);
+
+            store_killer(refs, killer);
+
+            assert_eq!(
+                &move_ordering(refs, None)[..5],
+                &[capture, killer, push, better, worse]
+            );
+        });
+    }
+
+    #[test]
+    fn quiet_cutoffs_score_in_the_history_table() {
+        with_refs(Board::default(), |refs| {
+            Search::negamax(refs, &mut Vec::new(), 4, -INFINITY, INFINITY, true);
+
+            let killers: Vec<ChessMove> = refs
+                .search_state
+                .killers
+                .iter()
+                .flatten()
+                .flatten()
+                .copied()
+                .collect();
+
+            assert!(!killers.is_empty());
+
+            // every killer was a quiet cutoff, and each of those adds depth squared
+            for killer in killers {
+                let score = refs.search_state.history_heuristic[killer.get_source().to_index()]
+                    [killer.get_dest().to_index()];
+
+                assert!(score > 0, "{killer}");
+            }
+        });
+    }
+
+    #[test]
+    fn null_moves_are_left_out_of_pawn_endings() {
+        // black to move is in zugzwang, every king move lets the white king escort the pawn home.
+        // with a pass allowed, the search would pick the drawn position where white has to move
+        let board = Board::from_str("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1").unwrap();
+
+        assert!(!has_non_pawn_material(&board));
+        assert!(!has_non_pawn_material(&board.null_move().unwrap()));
+
+        with_refs(board, |refs| {
+            let mut pv = Vec::new();
+            let mut eval = 0;
+
+            for depth in 1..=10 {
+                eval = Search::negamax(refs, &mut pv, depth, -INFINITY, INFINITY, true);
+            }
+
+            let rook = refs.eval_params.piece_values[Piece::Rook.to_index()];
+
+            assert!(eval < -rook, "{eval}");
+        });
+    }
+
+    #[test]
+    fn late_moves_are_reduced_more_but_never_into_quiescence() {
+        for depth in LMR_MIN_DEPTH..=MAX_PLY {
+            for move_index in LMR_MIN_MOVE_INDEX..64 {
+                let reduction = lmr_reduction(depth, move_index);
+
+                assert!((1..=depth - 2).contains(&reduction), "{depth} {move_index}");
+
+                assert!(reduction >= lmr_reduction(depth - 1, move_index).min(depth - 2));
+                assert!(reduction >= lmr_reduction(depth, move_index - 1));
+            }
+        }
+    }
+
+    #[test]
+    fn reductions_still_find_the_fork() {
+        let board = Board::from_str("3q3k/p5pp/8/4N3/8/4B3/5PPP/6K1 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            refs.limits = depth(6);
+
+            let (best_move, _) = Search::iterative_deepening(refs);
+
+            assert_eq!(best_move, Some(ChessMove::from_str("e5f7").unwrap()));
+        });
+    }
+
+    #[test]
+    fn only_positions_nobody_can_mate_in_are_insufficient() {
+        for (fen, insufficient) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1", true),
+            // bishops that all stand on one colour, whoever owns them
+            ("2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1", true),
+            ("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/P7/4K3 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", false),
+        ] {
+            with_refs(Board::from_str(fen).unwrap(), |refs| {
+                assert_eq!(is_insufficient_material(refs), insufficient, "{fen}");
+            });
+        }
+    }
+
+    #[test]
+    fn a_node_that_cannot_beat_a_shorter_mate_is_cut_before_searching() {
+        with_refs(Board::default(), |refs| {
+            play(refs, "e2e4");
+
+            // a mate already found one ply up is the best anything below can score
+            let eval = Search::negamax(refs, &mut Vec::new(), 5, INFINITY - 1, INFINITY, true);
+
+            assert_eq!(eval, INFINITY - 1);
+            assert_eq!(refs.search_state.nodes, 0);
+        });
+    }
+
+    #[test]
+    fn a_mate_scores_by_its_distance() {
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            let mut pv = Vec::new();
+
+            let eval = Search::negamax(refs, &mut pv, 4, -INFINITY, INFINITY, true);
+
+            assert_eq!(eval, INFINITY - 1);
+            assert_eq!(pv, [ChessMove::from_str("a1a8").unwrap()]);
+        });
+    }
+
+    fn play(refs: &mut SearchRefs, moves: &str) {
+        for m in moves.split_whitespace() {
+            let legal = ChessMove::from_str(m).unwrap();
+
+            assert!(refs.board.legal(legal), "{m} is illegal in {}", refs.board);
+
+            make_move(refs, legal);
+        }
+    }
+
+    // moves played before the search start count as game history rather than tree plies
+    fn play_in_game(refs: &mut SearchRefs, moves: &str) {
+        play(refs, moves);
+
+        refs.search_state.ply = 0;
+    }
+
+    fn infinite() -> SearchLimits {
+        SearchLimits {
+            infinite: true,
+            ..SearchLimits::default()
+        }
+    }
+
+    #[test]
+    fn searchmoves_restrict_the_root() {
+        let (search, report_rx) = spawn_search();
+
+        let [a3, h4, knight] = ["a2a3", "h2h4", "b8c6"].map(|m| ChessMove::from_str(m).unwrap());
+
+        search.send(EngineToSearch::Start(depth(4), vec![a3, h4]));
+        let restricted = wait_for_best_move(&report_rx);
+
+        // a list without a single legal move is ignored rather than leaving nothing to play
+        search.send(EngineToSearch::Start(depth(4), vec![knight]));
+        let ignored = wait_for_best_move(&report_rx);
+
+        search.send(EngineToSearch::Quit);
+
+        assert!([Some(a3), Some(h4)].contains(&restricted.best_move));
+        assert_eq!(restricted.pv_move, restricted.best_move);
+
+        assert!(ignored
+            .best_move
+            .is_some_and(|legal| Board::default().legal(legal)));
+    }
+
+    #[test]
+    fn multipv_reports_distinct_lines_best_first() {
+        let (search, report_rx) = spawn_search();
+
+        search.send(EngineToSearch::SetMultiPv(3));
+        search.send(EngineToSearch::Start(depth(5), Vec::new()));
+
+        let mut last_iteration = Vec::new();
+
+        let best_move = loop {
+            match report_rx.recv().unwrap() {
+                EngineReport::Search(SearchToEngine::Summary {
+                    depth: 5,
+                    multipv,
+                    cp,
+                    pv,
+                    ..
+                }) => last_iteration.push((multipv, cp, pv[0])),
+                EngineReport::Search(SearchToEngine::BestMove(best_move)) => break best_move,
+                _ => {}
+            }
+        };
+
+        search.send(EngineToSearch::Quit);
+
+        let numbers: Vec<u16> = last_iteration.iter().map(|line| line.0).collect();
+
+        assert_eq!(numbers, [1, 2, 3]);
+        assert_eq!(best_move, Some(last_iteration[0].2));
+
+        for pair in last_iteration.windows(2) {
+            assert!(pair[0].1 >= pair[1].1, "{last_iteration:?}");
+            assert_ne!(pair[0].2, pair[1].2);
+        }
+
+        assert_ne!(last_iteration[0].2, last_iteration[2].2);
+    }
+
+    #[test]
+    fn running_out_of_movetime_is_reported() {
+        let (search, report_rx) = spawn_search();
+
+        let limits = SearchLimits {
+            movetime: Some(Duration::milliseconds(50)),
+            ..SearchLimits::default()
+        };
+
+        search.send(EngineToSearch::Start(limits, Vec::new()));
+
+        let mut time_up = None;
+
+        loop {
+            match report_rx.recv().unwrap() {
+                EngineReport::Search(SearchToEngine::TimeUp {
+                    hard,
+                    elapsed,
+                    depth,
+                    ..
+                }) => time_up = Some((hard, elapsed, depth)),
+                EngineReport::Search(SearchToEngine::BestMove(_)) => break,
+                _ => {}
+            }
+        }
+
+        search.send(EngineToSearch::Quit);
+
+        let movetime = std::time::Duration::from_millis(50);
+
+        let (hard, elapsed, depth) = time_up.expect("no time up report");
+
+        assert_eq!(hard, movetime);
+        assert!(elapsed >= movetime);
+        assert!(depth > 0);
+    }
+
+    #[test]
+    fn a_short_clock_stays_inside_the_hard_limit() {
+        let (search, report_rx) = spawn_search();
+
+        let limits = SearchLimits {
+            game_time: Some(GameTime {
+                white_time: Duration::seconds(2),
+                black_time: Duration::seconds(2),
+                white_increment: Duration::zero(),
+                black_increment: Duration::zero(),
+                moves_to_go: None,
+            }),
+            ..SearchLimits::default()
+        };
+
+        let start = Instant::now();
+
+        search.send(EngineToSearch::Start(limits, Vec::new()));
+
+        let mut allocated = None;
+
+        let best_move = loop {
+            match report_rx.recv().unwrap() {
+                EngineReport::Search(SearchToEngine::TimeAllocated { soft, hard }) => {
+                    allocated = Some((soft, hard))
+                }
+                EngineReport::Search(SearchToEngine::BestMove(best_move)) => break best_move,
+                _ => {}
+            }
+        };
+
+        let elapsed = start.elapsed();
+
+        search.send(EngineToSearch::Quit);
+
+        let (soft, hard) = allocated.expect("no time was allocated");
+
+        assert!(best_move.is_some());
+        assert!(soft <= hard);
+
+        // some slack for the search thread to notice and report back
+        assert!(
+            elapsed < hard + std::time::Duration::from_millis(100),
+            "{elapsed:?}"
+        );
+    }
+
+    #[test]
+    fn a_stop_right_after_go_still_answers_a_legal_move() {
+        let (search, report_rx) = spawn_search();
+
+        for _ in 0..10 {
+            search.send(EngineToSearch::Start(infinite(), Vec::new()));
+            search.send(EngineToSearch::Stop);
+
+            let best_move = wait_for_best_move(&report_rx).best_move;
+
+            assert!(best_move.is_some_and(|legal| Board::default().legal(legal)));
+        }
+
+        search.send(EngineToSearch::Quit);
+    }
+
+    #[test]
+    fn a_finished_game_has_no_best_move() {
+        // checkmated and stalemated
+        for fen in [
+            "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1",
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+        ] {
+            with_refs(Board::from_str(fen).unwrap(), |refs| {
+                refs.limits = depth(5);
+
+                let (best_move, _) = Search::iterative_deepening(refs);
+
+                assert_eq!(best_move, None);
+                assert_eq!(refs.search_state.nodes, 0);
+            });
+        }
+    }
+
+    #[test]
+    fn currmove_is_reported_only_once_a_search_runs_long() {
+        let (search, report_rx) = spawn_search();
+
+        let currmoves = |limits: SearchLimits, stop_after: Option<std::time::Duration>| {
+            search.send(EngineToSearch::Start(limits, Vec::new()));
+
+            if let Some(stop_after) = stop_after {
+                std::thread::sleep(stop_after);
+                search.send(EngineToSearch::Stop);
+            }
+
+            let mut currmoves = Vec::new();
+
+            loop {
+                match report_rx.recv().unwrap() {
+                    EngineReport::Search(SearchToEngine::CurrMove { mv, number }) => {
+                        currmoves.push((mv, number))
+                    }
+                    EngineReport::Search(SearchToEngine::BestMove(_)) => return currmoves,
+                    _ => {}
+                }
+            }
+        };
+
+        let quick = currmoves(depth(3), None);
+        let slow = currmoves(infinite(), Some(CURRMOVE_DELAY * 3 / 2));
+
+        search.send(EngineToSearch::Quit);
+
+        assert!(quick.is_empty());
+        assert!(!slow.is_empty());
+
+        let root_moves = MoveGen::new_legal(&Board::default()).len() as u16;
+
+        for (mv, number) in slow {
+            assert!(Board::default().legal(mv));
+            assert!((1..=root_moves).contains(&number), "{number}");
+        }
+    }
+
+    #[test]
+    fn progress_is_reported_while_an_iteration_runs_long() {
+        const REPORTS: usize = 2;
+
+        let (search, report_rx) = spawn_search();
+
+        // an infinite search is stopped once it has reported enough
+        let progress = |limits: SearchLimits| {
+            search.send(EngineToSearch::Start(limits, Vec::new()));
+
+            let mut summarised = 0;
+            let mut progress = Vec::new();
+
+            loop {
+                let report = report_rx
+                    .recv_timeout(std::time::Duration::from_secs(60))
+                    .expect("search never reported its progress");
+
+                match report {
+                    EngineReport::Search(SearchToEngine::Summary { depth, .. }) => {
+                        summarised = depth
+                    }
+                    // always about the iteration still running, never one already summarised
+                    EngineReport::Search(SearchToEngine::Progress { depth, nodes, .. }) => {
+                        assert!(depth > summarised, "progress at {depth} after {summarised}");
+
+                        progress.push(nodes);
+
+                        if progress.len() == REPORTS {
+                            search.send(EngineToSearch::Stop);
+                        }
+                    }
+                    EngineReport::Search(SearchToEngine::BestMove(_)) => return progress,
+                    _ => {}
+                }
+            }
+        };
+
+        let quick = progress(depth(3));
+        let slow = progress(infinite());
+
+        search.send(EngineToSearch::Quit);
+
+        assert!(quick.is_empty());
+        assert!(slow.len() >= REPORTS);
+        assert!(slow.is_sorted());
+    }
+
+    #[test]
+    fn helpers_stop_with_the_main_thread() {
+        let (search, report_rx) = spawn_search();
+
+        search.send(EngineToSearch::SetThreads(2));
+        search.send(EngineToSearch::Start(infinite(), Vec::new()));
+
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        search.send(EngineToSearch::Stop);
+
+        let reported = wait_for_best_move(&report_rx);
+        search.send(EngineToSearch::Quit);
+
+        assert!(Board::default().legal(reported.best_move.unwrap()));
+    }
+
+    #[test]
+    fn helpers_do_not_change_the_depth_limit() {
+        let (search, report_rx) = spawn_search();
+
+        search.send(EngineToSearch::SetThreads(2));
+        search.send(EngineToSearch::Start(depth(4), Vec::new()));
+
+        let reported = wait_for_best_move(&report_rx);
+        search.send(EngineToSearch::Quit);
+
+        assert!(Board::default().legal(reported.best_move.unwrap()));
+        assert_eq!(reported.deepest, 4);
+        assert_eq!(reported.best_move, reported.pv_move);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn a_panicking_search_is_caught_and_explained() {
+        with_refs(Board::default(), |refs| {
+            refs.limits = depth(1);
+
+            // a ply nobody made stands in for a bug somewhere down the tree
+            refs.search_state.ply = 1;
+
+            let payload = Search::lazy_smp(refs, 1).expect_err("the search did not panic");
+
+            assert_ne!(panic_message(payload.as_ref()), "unknown panic");
+        });
+
+        let payload = std::panic::catch_unwind(|| panic!("{} nodes", 42)).unwrap_err();
+
+        assert_eq!(panic_message(payload.as_ref()), "42 nodes");
+    }
+
+    #[test]
+    fn a_stop_mid_iteration_keeps_a_root_move_that_already_improved() {
+        let search = |limits: SearchLimits| {
+            with_refs(Board::default(), |refs| {
+                refs.limits = limits;
+
+                let (best_move, _) = Search::iterative_deepening(refs);
+
+                (best_move, refs.search_state.completed_depth)
+            })
+        };
+
+        let (completed, _) = search(depth(4));
+        let (deeper, _) = search(depth(5));
+
+        assert_ne!(completed, deeper);
+
+        // enough nodes for depth 4 and the part of depth 5 that switches moves
+        let (best_move, completed_depth) = search(SearchLimits {
+            max_nodes: Some(7500),
+            ..SearchLimits::default()
+        });
+
+        assert_eq!(completed_depth, 4);
+        assert_eq!(best_move, deeper);
+    }
+
+    #[test]
+    fn settings_sent_during_a_search_apply_to_the_next() {
+        let (search, report_rx) = spawn_search();
+
+        search.send(EngineToSearch::Start(infinite(), Vec::new()));
+        search.send(EngineToSearch::SetMultiPv(3));
+        search.send(EngineToSearch::SetHashSize(MIN_HASH_SIZE));
+        search.send(EngineToSearch::Stop);
+
+        wait_for_best_move(&report_rx);
+
+        search.send(EngineToSearch::Start(depth(2), Vec::new()));
+
+        let reported = wait_for_best_move(&report_rx);
+        search.send(EngineToSearch::Quit);
+
+        assert_eq!(reported.lines, 3);
+    }
+
+    #[test]
+    fn aspiration_failures_are_counted() {
+        let (search, report_rx) = spawn_search();
+
+        // a one centipawn window fails almost every iteration, without growth each side then
+        // opens all the way at once
+        search.send(EngineToSearch::SetAspirationDelta(1));
+        search.send(EngineToSearch::Start(depth(7), Vec::new()));
+
+        let reported = wait_for_best_move(&report_rx);
+        search.send(EngineToSearch::Quit);
+
+        assert!(!reported.researched.is_empty());
+
+        for &(fail_lows, fail_highs) in &reported.researched {
+            assert!(
+                fail_lows <= 1 && fail_highs <= 1,
+                "{fail_lows} {fail_highs}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_tiny_window_re_searches_more() {
+        let (search, report_rx) = spawn_search();
+
+        let mut researches = Vec::new();
+
+        // the defaults, then a one centipawn window that doubles its step on every failure
+        for (delta, growth) in [(DEFAULT_ASPIRATION_DELTA, 0), (1, 200)] {
+            search.send(EngineToSearch::NewGame);
+            search.send(EngineToSearch::SetAspirationDelta(delta));
+            search.send(EngineToSearch::SetAspirationGrowth(growth));
+            search.send(EngineToSearch::Start(depth(7), Vec::new()));
+
+            let reported = wait_for_best_move(&report_rx);
+
+            let total: u16 = reported
+                .researched
+                .iter()
+                .map(|(fail_lows, fail_highs)| fail_lows + fail_highs)
+                .sum();
+
+            researches.push(total);
+        }
+
+        search.send(EngineToSearch::Quit);
+
+        assert!(researches[1] > researches[0], "{researches:?}");
+    }
+
+    #[test]
+    fn new_game_forgets_the_last_search() {
+        let (search, report_rx) = spawn_search();
+
+        let mut nodes = Vec::new();
+
+        // cold, then warmed by the first search, then cold again
+        for new_game in [false, false, true] {
+            if new_game {
+                search.send(EngineToSearch::NewGame);
+            }
+
+            search.send(EngineToSearch::Start(depth(6), Vec::new()));
+
+            nodes.push(wait_for_best_move(&report_rx).nodes);
+        }
+
+        search.send(EngineToSearch::Quit);
+
+        assert!(nodes[1] < nodes[0], "{nodes:?}");
+        assert_eq!(nodes[2], nodes[0]);
+    }
+
+    #[test]
+    fn every_root_search_ages_the_table_once() {
+        with_refs(Board::default(), |refs| {
+            refs.limits = depth(3);
+
+            for generation in 1..=2 {
+                *refs.search_state = SearchState {
+                    start_time: Some(Instant::now()),
+                    ..SearchState::default()
+                };
+
+                // the last search left the stop flag raised for its helpers
+                refs.shared.stop.store(false, Ordering::Relaxed);
+
+                Search::lazy_smp(refs, 1).unwrap();
+
+                let root = refs.tt.probe(refs.board.get_hash(), 0).unwrap();
+
+                assert_eq!(root.generation, generation);
+            }
+        });
+    }
+
+    #[test]
+    fn checks_extend_no_further_than_the_root_depth() {
+        // both queens can check nearly every move
+        let board = Board::from_str("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+
+        for (already, expected) in [(2, 3), (3, 3)] {
+            with_refs(board, |refs| {
+                play(refs, "d1a4");
+
+                refs.search_state.depth = 3;
+                refs.search_state.extensions[0] = already;
+
+                Search::negamax(refs, &mut Vec::new(), 1, -INFINITY, INFINITY, true);
+
+                assert_eq!(refs.search_state.extensions[1], expected);
+            });
+        }
+
+        with_refs(board, |refs| {
+            refs.limits = depth(5);
+
+            let (best_move, _) = Search::iterative_deepening(refs);
+
+            assert!(best_move.is_some());
+            assert_eq!(refs.search_state.completed_depth, 5);
+            assert!(refs.search_state.nodes < 100_000);
+        });
+    }
+
+    #[test]
+    fn futility_skips_quiets_but_not_captures_far_below_alpha() {
+        // a knight against a queen, with a pawn to take
+        let board = Board::from_str("q3k3/8/8/8/8/2p5/8/1N2K3 b - - 0 1").unwrap();
+
+        let nodes = [0, -2000].map(|alpha| {
+            with_refs(board, |refs| {
+                play(refs, "e8f8");
+
+                // an open beta keeps reverse futility out of it
+                Search::negamax(refs, &mut Vec::new(), 1, alpha, INFINITY, true);
+
+                refs.search_state.nodes
+            })
+        });
+
+        // the root and the capture, which drops into quiescence at its child
+        assert_eq!(nodes[0], 3);
+        assert!(nodes[1] > nodes[0]);
+    }
+
+    #[test]
+    fn reverse_futility_returns_the_static_eval_far_above_beta() {
+        // a queen up at a shallow node
+        let board = Board::from_str("4k3/8/8/8/8/8/3Q4/4K3 b - - 0 1").unwrap();
+
+        let nodes = [0, 2000].map(|beta| {
+            with_refs(board, |refs| {
+                play(refs, "e8f7");
+
+                let eval = Search::negamax(refs, &mut Vec::new(), 2, beta - 1, beta, true);
+
+                if beta == 0 {
+                    assert_eq!(eval, static_eval(refs));
+                }
+
+                refs.search_state.nodes
+            })
+        });
+
+        assert_eq!(nodes[0], 1);
+        assert!(nodes[1] > nodes[0]);
+
+        // the side behind on material still finds its back rank mate
+        let board = Board::from_str("r5k1/5ppp/8/8/7Q/8/5PPP/6K1 b - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            refs.limits = depth(3);
+
+            let (best_move, _) = Search::iterative_deepening(refs);
+
+            assert_eq!(best_move, Some(ChessMove::from_str("a8a1").unwrap()));
+        });
+    }
+
+    #[test]
+    fn an_improving_node_prunes_less() {
+        let params = EvalParams::default();
+
+        for depth in 1..=FUTILITY_MAX_DEPTH {
+            assert!(futility_margin(&params, depth, true) > futility_margin(&params, depth, false));
+        }
+
+        let board = Board::from_str("q3k3/8/8/8/8/2p5/8/1N2K3 w - - 0 1").unwrap();
+
+        // the eval two plies up decides, a node after a check has none to compare with
+        let nodes = [Some(INFINITY), None, Some(-INFINITY)].map(|previous| {
+            with_refs(board, |refs| {
+                play(refs, "e1f1 e8f8");
+
+                refs.search_state.static_evals[0] = previous;
+
+                let margin = futility_margin(refs.eval_params, 1, false);
+                let alpha = (static_eval(refs) as i32 + margin) as Eval;
+
+                Search::negamax(refs, &mut Vec::new(), 1, alpha, INFINITY, true);
+
+                refs.search_state.nodes
+            })
+        });
+
+        assert_eq!(nodes[0], nodes[1]);
+        assert!(nodes[2] > nodes[1]);
+    }
+
+    #[test]
+    fn singular_extension_only_deepens_the_singular_move() {
+        const DEPTH: u8 = SINGULAR_MIN_DEPTH;
+
+        let board = Board::from_str("k7/6p1/8/8/8/8/P4Q2/7K w - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            // the queen blunders onto f6, where taking it is the only move that doesn't lose
+            play(refs, "f2f6");
+
+            let capture = ChessMove::from_str("g7f6").unwrap();
+
+            refs.search_state.depth = DEPTH;
+            refs.tt.store(
+                refs.board.get_hash(),
+                DEPTH - SINGULAR_TT_DEPTH_MARGIN + 1,
+                300,
+                Bound::Lower,
+                Some(capture),
+                refs.search_state.ply,
+            );
+
+            let mut pv = Vec::new();
+
+            let eval = Search::negamax(refs, &mut pv, DEPTH, -INFINITY, INFINITY, true);
+
+            assert_eq!(pv.first(), Some(&capture));
+
+            let queen = refs.eval_params.piece_values[Piece::Queen.to_index()];
+
+            assert!(eval > -queen / 2, "{eval}");
+
+            // the capture was searched a ply deeper than its siblings
+            let after_capture = refs.board.make_move_new(capture);
+            let entry = refs.tt.probe(after_capture.get_hash(), 2).unwrap();
+
+            assert_eq!(entry.depth, DEPTH);
+
+            // and the siblings searched after it did not inherit its extension
+            assert_eq!(refs.search_state.extensions[1], 0);
+        });
+    }
+
+    const KNIGHT_SHUFFLE: &str = "g1f3 g8f6 f3g1 f6g8";
+
+    #[test]
+    fn contempt_avoids_a_repetition_that_would_otherwise_be_taken() {
+        // a pawn down, black would rather repeat, unless the draw costs it the full contempt
+        let board =
+            Board::from_str("rnbqkbnr/pp1ppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+
+        let repeat = ChessMove::from_str("f6g8").unwrap();
+
+        let best_moves = [0, MAX_CONTEMPT].map(|contempt| {
+            with_refs(board, |refs| {
+                play_in_game(refs, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1");
+
+                refs.contempt = contempt;
+                refs.limits = depth(5);
+
+                Search::iterative_deepening(refs).0.unwrap()
+            })
+        });
+
+        assert_eq!(best_moves[0], repeat);
+        assert_ne!(best_moves[1], repeat);
+    }
+
+    #[test]
+    fn game_repetition_needs_threefold() {
+        with_refs(Board::default(), |refs| {
+            play_in_game(refs, KNIGHT_SHUFFLE);
+            assert!(!is_repetition(refs));
+
+            play_in_game(refs, KNIGHT_SHUFFLE);
+            assert!(is_repetition(refs));
+        });
+    }
+
+    #[test]
+    fn tree_repetition_counts_once() {
+        with_refs(Board::default(), |refs| {
+            play(refs, "e2e4 e7e5");
+            play(refs, KNIGHT_SHUFFLE);
+
+            assert!(is_repetition(refs));
+        });
+    }
+
+    #[test]
+    fn root_repetition_is_game_history() {
+        with_refs(Board::default(), |refs| {
+            play(refs, KNIGHT_SHUFFLE);
+
+            assert!(!is_repetition(refs));
+        });
+    }
+
+    #[test]
+    fn a_repeat_from_before_an_irreversible_move_does_not_count() {
+        with_refs(Board::default(), |refs| {
+            play_in_game(refs, KNIGHT_SHUFFLE);
+
+            // as if the last move had been a capture, the copy of the start position before it
+            // is out of reach
+            refs.history.last_mut().unwrap().halfmove_clock = 0;
+
+            play_in_game(refs, KNIGHT_SHUFFLE);
+            assert!(!is_repetition(refs));
+        });
+    }
+
+    #[test]
+    fn lost_castling_rights_are_not_a_repetition() {
+        let board = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            play(refs, "a1b1 a8b8 b1a1 b8a8");
+            assert!(!is_repetition(refs));
+
+            // without the rights the same shuffle does come back to the same position
+            play(refs, "a1b1 a8b8 b1a1 b8a8");
+            assert!(is_repetition(refs));
+        });
+    }
+
+    #[test]
+    fn lost_en_passant_is_not_a_repetition() {
+        let board = Board::from_str("4k1n1/3p4/8/4P3/8/8/8/4K1N1 b - - 0 1").unwrap();
+
+        with_refs(board, |refs| {
+            play(refs, "d7d5");
+            assert!(refs.board.en_passant().is_some());
+
+            play(refs, KNIGHT_SHUFFLE);
+            assert!(!is_repetition(refs));
+        });
+    }
+}
@@ -0,0 +1,129 @@
+use crate::evaluate::Eval;
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, ChessMove, Color, Piece, Square, ALL_PIECES, EMPTY,
+};
+
+pub fn see(board: &Board, legal: ChessMove, piece_values: &[Eval; 6]) -> i32 {
+    let target = legal.get_dest();
+    let source = legal.get_source();
+
+    let mut attacker = board.piece_on(source).unwrap();
+
+    let is_en_passant = attacker == Piece::Pawn
+        && board.en_passant() == Some(target.ubackward(board.side_to_move()));
+
+    let captured = match board.piece_on(target) {
+        Some(piece) => piece,
+        None if is_en_passant => Piece::Pawn,
+        None => return 0,
+    };
+
+    let mut gain = [0i32; 32];
+    let mut depth = 0;
+
+    let mut occupied = *board.combined() ^ BitBoard::from_square(source);
+    let mut side = !board.side_to_move();
+
+    let value = |piece: Piece| piece_values[piece.to_index()] as i32;
+
+    gain[0] = value(captured);
+
+    loop {
+        let attackers = attackers_to(board, target, occupied) & board.color_combined(side);
+
+        let Some((square, piece)) = least_valuable_attacker(board, attackers) else {
+            break;
+        };
+
+        depth += 1;
+
+        gain[depth] = value(attacker) - gain[depth - 1];
+
+        if depth == gain.len() - 1 {
+            break;
+        }
+
+        occupied ^= BitBoard::from_square(square);
+        attacker = piece;
+        side = !side;
+    }
+
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+
+        depth -= 1;
+    }
+
+    gain[0]
+}
+
+pub fn attackers_to(board: &Board, square: Square, occupied: BitBoard) -> BitBoard {
+    let pawns = board.pieces(Piece::Pawn);
+    let bishops = board.pieces(Piece::Bishop) | board.pieces(Piece::Queen);
+    let rooks = board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+
+    let attackers = get_pawn_attacks(
+        square,
+        Color::Black,
+        pawns & board.color_combined(Color::White),
+    ) | get_pawn_attacks(
+        square,
+        Color::White,
+        pawns & board.color_combined(Color::Black),
+    ) | get_knight_moves(square) & board.pieces(Piece::Knight)
+        | get_king_moves(square) & board.pieces(Piece::King)
+        | get_bishop_moves(square, occupied) & bishops
+        | get_rook_moves(square, occupied) & rooks;
+
+    attackers & occupied
+}
+
+fn least_valuable_attacker(board: &Board, attackers: BitBoard) -> Option<(Square, Piece)> {
+    if attackers == EMPTY {
+        return None;
+    }
+
+    ALL_PIECES.into_iter().find_map(|piece| {
+        let pieces = attackers & board.pieces(piece);
+
+        (pieces != EMPTY).then(|| (pieces.to_square(), piece))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::EvalParams;
+    use std::str::FromStr;
+
+    #[test]
+    fn exchanges_add_up() {
+        let piece_values = EvalParams::default().piece_values;
+
+        let [pawn, knight, _, rook, queen, _] = piece_values.map(i32::from);
+
+        for (fen, m, exchange) in [
+            // a free pawn, and one taken en passant
+            ("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5", pawn),
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", pawn),
+            // a pawn takes a knight and is taken back
+            ("4k3/2p5/3n4/4P3/8/8/8/4K3 w - - 0 1", "e5d6", knight - pawn),
+            // the queen grabs a defended pawn
+            ("4k3/2p5/3p4/8/8/8/3Q4/4K3 w - - 0 1", "d2d6", pawn - queen),
+            // the rook behind the first one recaptures through it
+            ("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5", pawn),
+            // the king can't take back on a defended square, but can on an undefended one
+            ("8/8/8/3k4/3p4/8/3R4/3RK3 w - - 0 1", "d2d4", pawn),
+            ("8/8/8/3k4/3p4/8/3R4/4K3 w - - 0 1", "d2d4", pawn - rook),
+            // a move that captures nothing
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", "e1e2", 0),
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            let legal = ChessMove::from_str(m).unwrap();
+
+            assert!(board.legal(legal), "{m} is illegal in {fen}");
+            assert_eq!(see(&board, legal, &piece_values), exchange, "{m} in {fen}");
+        }
+    }
+}
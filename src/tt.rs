@@ -0,0 +1,404 @@
+use crate::{evaluate::Eval, search::INFINITY};
+use chess::{ChessMove, ALL_PIECES, ALL_SQUARES};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+pub const DEFAULT_HASH_SIZE: usize = 16;
+pub const MIN_HASH_SIZE: usize = 1;
+pub const MAX_HASH_SIZE: usize = 1024;
+
+const OCCUPIED_BIT: u64 = 1 << 63;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TtEntry {
+    pub hash: u64,
+    pub depth: u8,
+    pub eval: Eval,
+    pub bound: Bound,
+    pub best_move: Option<ChessMove>,
+    pub generation: u8,
+}
+
+// the key is stored xored with the data, so a slot torn by two writers fails the hash check
+#[derive(Debug, Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let slot_count = size_mb * 1024 * 1024 / std::mem::size_of::<Slot>();
+
+        TranspositionTable {
+            slots: (0..slot_count.max(1)).map(|_| Slot::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn resize(&mut self, size_mb: usize) {
+        *self = TranspositionTable::new(size_mb);
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot.key.get_mut() = 0;
+            *slot.data.get_mut() = 0;
+        }
+    }
+
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, hash: u64, ply: u8) -> Option<TtEntry> {
+        self.load(self.slot(hash))
+            .filter(|entry| entry.hash == hash)
+            .map(|mut entry| {
+                entry.eval = eval_from_tt(entry.eval, ply);
+
+                entry
+            })
+    }
+
+    pub fn store(
+        &self,
+        hash: u64,
+        depth: u8,
+        eval: Eval,
+        bound: Bound,
+        best_move: Option<ChessMove>,
+        ply: u8,
+    ) {
+        let generation = self.generation.load(Ordering::Relaxed);
+
+        let slot = self.slot(hash);
+
+        // deeper entries survive unless they are left over from an earlier search
+        if let Some(existing) = self.load(slot) {
+            let relevant = existing.hash == hash || existing.generation == generation;
+
+            if relevant && existing.depth > depth && bound != Bound::Exact {
+                return;
+            }
+        }
+
+        let data = pack(&TtEntry {
+            hash,
+            depth,
+            eval: eval_to_tt(eval, ply),
+            bound,
+            best_move,
+            generation,
+        });
+
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn hashfull(&self) -> u16 {
+        let sample = &self.slots[..self.slots.len().min(1000)];
+
+        let generation = self.generation.load(Ordering::Relaxed);
+
+        let used = sample
+            .iter()
+            .filter_map(|slot| self.load(slot))
+            .filter(|entry| entry.generation == generation)
+            .count();
+
+        (used * 1000 / sample.len()) as u16
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        &self.slots[(hash % self.slots.len() as u64) as usize]
+    }
+
+    fn load(&self, slot: &Slot) -> Option<TtEntry> {
+        let key = slot.key.load(Ordering::Relaxed);
+        let data = slot.data.load(Ordering::Relaxed);
+
+        if data & OCCUPIED_BIT == 0 {
+            return None;
+        }
+
+        Some(unpack(key ^ data, data))
+    }
+}
+
+// depth 8 bits, eval 16, bound 2, move 16, generation 8, and the occupied bit at the top
+fn pack(entry: &TtEntry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+
+    let best_move = entry.best_move.map_or(0, |legal| {
+        let promotion = legal
+            .get_promotion()
+            .map_or(0, |piece| piece.to_index() as u64 + 1);
+
+        1 | (legal.get_source().to_index() as u64) << 1
+            | (legal.get_dest().to_index() as u64) << 7
+            | promotion << 13
+    });
+
+    entry.depth as u64
+        | (entry.eval as u16 as u64) << 8
+        | bound << 24
+        | best_move << 26
+        | (entry.generation as u64) << 42
+        | OCCUPIED_BIT
+}
+
+fn unpack(hash: u64, data: u64) -> TtEntry {
+    let bound = match (data >> 24) & 0b11 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+
+    let best_move = (data >> 26) & 0xffff;
+
+    let best_move = (best_move & 1 == 1).then(|| {
+        let promotion = match (best_move >> 13) & 0b111 {
+            0 => None,
+            piece => Some(ALL_PIECES[piece as usize - 1]),
+        };
+
+        ChessMove::new(
+            ALL_SQUARES[((best_move >> 1) & 0x3f) as usize],
+            ALL_SQUARES[((best_move >> 7) & 0x3f) as usize],
+            promotion,
+        )
+    });
+
+    TtEntry {
+        hash,
+        depth: data as u8,
+        eval: (data >> 8) as u16 as Eval,
+        bound,
+        best_move,
+        generation: (data >> 42) as u8,
+    }
+}
+
+// mate scores are stored relative to the node rather than the root
+fn eval_to_tt(eval: Eval, ply: u8) -> Eval {
+    if eval > INFINITY / 2 {
+        eval + ply as Eval
+    } else if eval < -INFINITY / 2 {
+        eval - ply as Eval
+    } else {
+        eval
+    }
+}
+
+fn eval_from_tt(eval: Eval, ply: u8) -> Eval {
+    if eval > INFINITY / 2 {
+        eval - ply as Eval
+    } else if eval < -INFINITY / 2 {
+        eval + ply as Eval
+    } else {
+        eval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_search_increments_generation() {
+        let tt = TranspositionTable::new(MIN_HASH_SIZE);
+
+        tt.store(1, 1, 0, Bound::Exact, None, 0);
+        assert_eq!(tt.probe(1, 0).unwrap().generation, 0);
+
+        tt.new_search();
+        tt.store(2, 1, 0, Bound::Exact, None, 0);
+
+        assert_eq!(tt.probe(2, 0).unwrap().generation, 1);
+    }
+
+    #[test]
+    fn stale_entries_give_way_and_current_deeper_ones_stay() {
+        let tt = TranspositionTable::new(MIN_HASH_SIZE);
+
+        let slot_count = tt.slots.len() as u64;
+
+        // a whole table of deep entries, then a colliding shallower key for every slot
+        for key in 0..slot_count {
+            tt.store(key, 9, 0, Bound::Lower, None, 0);
+        }
+
+        for key in 0..slot_count {
+            tt.store(key + slot_count, 3, 0, Bound::Lower, None, 0);
+        }
+
+        assert!((0..slot_count).all(|key| tt.probe(key, 0).is_some_and(|entry| entry.depth == 9)));
+        assert!((0..slot_count).all(|key| tt.probe(key + slot_count, 0).is_none()));
+
+        // a search later the deep entries are stale, so the shallower ones take their slots
+        tt.new_search();
+
+        for key in 0..slot_count {
+            tt.store(key + slot_count, 3, 0, Bound::Lower, None, 0);
+        }
+
+        assert!((0..slot_count).all(|key| tt.probe(key, 0).is_none()));
+        assert!((0..slot_count).all(|key| tt.probe(key + slot_count, 0).is_some()));
+
+        // within a search only an exact score replaces a deeper entry
+        tt.store(slot_count, 1, 0, Bound::Upper, None, 0);
+        assert_eq!(tt.probe(slot_count, 0).unwrap().depth, 3);
+
+        tt.store(slot_count, 1, 0, Bound::Exact, None, 0);
+        assert_eq!(tt.probe(slot_count, 0).unwrap().depth, 1);
+    }
+
+    #[test]
+    fn hashfull_counts_this_search_in_permille() {
+        let tt = TranspositionTable::new(MIN_HASH_SIZE);
+
+        assert_eq!(tt.hashfull(), 0);
+
+        // the sample is the first thousand slots, and consecutive keys land in consecutive slots
+        for key in 0..250 {
+            tt.store(key, 1, 0, Bound::Exact, None, 0);
+        }
+
+        assert_eq!(tt.hashfull(), 250);
+
+        // entries from the last search are free to be replaced, so they don't count
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+
+        tt.store(0, 1, 0, Bound::Exact, None, 0);
+        assert_eq!(tt.hashfull(), 1);
+    }
+
+    #[test]
+    fn clear_empties_the_table() {
+        let mut tt = TranspositionTable::new(MIN_HASH_SIZE);
+
+        for key in 0..1000 {
+            tt.store(key, 4, 0, Bound::Exact, None, 0);
+        }
+
+        assert_eq!(tt.hashfull(), 1000);
+
+        tt.clear();
+
+        assert!((0..1000).all(|key| tt.probe(key, 0).is_none()));
+        assert_eq!(tt.hashfull(), 0);
+    }
+
+    #[test]
+    fn a_torn_slot_is_rejected() {
+        let tt = TranspositionTable::new(MIN_HASH_SIZE);
+
+        let first = 3;
+        let second = first + tt.slots.len() as u64;
+
+        let slot = tt.slot(first);
+
+        tt.store(first, 5, 120, Bound::Lower, None, 0);
+        let first_key = slot.key.load(Ordering::Relaxed);
+        let first_data = slot.data.load(Ordering::Relaxed);
+
+        tt.store(second, 9, -40, Bound::Exact, None, 0);
+        assert_eq!(tt.probe(second, 0).unwrap().depth, 9);
+
+        // a reader caught between the two stores sees one writer's key with the other's data
+        slot.key.store(first_key, Ordering::Relaxed);
+        assert!(tt.probe(first, 0).is_none());
+        assert!(tt.probe(second, 0).is_none());
+
+        slot.data.store(first_data, Ordering::Relaxed);
+        assert_eq!(tt.probe(first, 0).unwrap().depth, 5);
+    }
+
+    // everything stored under a key follows from the key, so a torn entry can't pass as a real one
+    fn expected(key: u64) -> (u8, Eval, ChessMove) {
+        let n = key as usize;
+
+        let depth = (n % 200) as u8;
+        let eval = (n % 2000) as Eval - 1000;
+        let best_move = ChessMove::new(ALL_SQUARES[n % 64], ALL_SQUARES[(n / 64) % 64], None);
+
+        (depth, eval, best_move)
+    }
+
+    #[test]
+    fn threads_never_read_a_torn_entry() {
+        const THREADS: u64 = 4;
+        const SLOTS: u64 = 8;
+        const KEYS_PER_SLOT: u64 = 64;
+        const ROUNDS: u64 = 200_000;
+
+        let tt = TranspositionTable::new(MIN_HASH_SIZE);
+
+        // a handful of slots, each shared by many keys, keeps every thread writing over the others
+        let slot_count = tt.slots.len() as u64;
+        let keys: Vec<u64> = (0..SLOTS)
+            .flat_map(|slot| (1..=KEYS_PER_SLOT).map(move |n| slot + n * slot_count))
+            .collect();
+
+        let accepted: u64 = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|thread| {
+                    let tt = &tt;
+                    let keys = &keys;
+
+                    scope.spawn(move || {
+                        let mut accepted = 0;
+
+                        for round in 0..ROUNDS {
+                            let index = (round * 7 + thread * 13) as usize;
+
+                            let key = keys[index % keys.len()];
+                            let (depth, eval, best_move) = expected(key);
+
+                            tt.store(key, depth, eval, Bound::Exact, Some(best_move), 0);
+
+                            let key = keys[(index * 31 + 5) % keys.len()];
+
+                            if let Some(entry) = tt.probe(key, 0) {
+                                let stored = (entry.depth, entry.eval, entry.best_move.unwrap());
+
+                                assert_eq!(stored, expected(key), "torn entry for {key}");
+                                assert_eq!(entry.bound, Bound::Exact);
+
+                                accepted += 1;
+                            }
+                        }
+
+                        accepted
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .sum()
+        });
+
+        // the probes must have found something, or there was nothing to check
+        assert!(accepted > 0);
+    }
+}
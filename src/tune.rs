@@ -0,0 +1,144 @@
+use crate::{
+    evaluate::{evaluate, game_phase, material},
+    params::EvalParams,
+};
+use chess::{Board, Color};
+use std::str::FromStr;
+
+const SIGMOID_K: f64 = 1.0;
+
+pub fn run(path: &str, eval_file: Option<&str>) {
+    let params = match eval_file.map(EvalParams::from_file) {
+        Some(Ok(params)) => params,
+        Some(Err(err)) => {
+            eprintln!("{err}");
+
+            return;
+        }
+        None => EvalParams::default(),
+    };
+
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+
+            return;
+        }
+    };
+
+    let (error, positions, skipped) = mean_squared_error(&text, &params);
+
+    if skipped > 0 {
+        eprintln!("skipped {skipped} unreadable lines");
+    }
+
+    println!("{} positions {:.6} error", positions, error);
+}
+
+// the error, the positions it is over and the lines that could not be read
+fn mean_squared_error(text: &str, params: &EvalParams) -> (f64, usize, usize) {
+    let mut total_error = 0.0;
+    let mut positions = 0;
+    let mut skipped = 0;
+
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let Some((board, result)) = parse_line(line) else {
+            skipped += 1;
+
+            continue;
+        };
+
+        let eval = evaluate(&board, game_phase(&board), material(&board, params), params) as f64;
+
+        // labels are from white's point of view, the eval is from the side to move's
+        let white_eval = match board.side_to_move() {
+            Color::White => eval,
+            Color::Black => -eval,
+        };
+
+        total_error += (result - sigmoid(white_eval)).powi(2);
+        positions += 1;
+    }
+
+    let error = match positions {
+        0 => 0.0,
+        _ => total_error / positions as f64,
+    };
+
+    (error, positions, skipped)
+}
+
+fn sigmoid(eval: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-SIGMOID_K * eval / 400.0))
+}
+
+// accepts `<fen> [1.0]`, `<fen> "1-0"` and epd `c9 "1-0";` style labels
+fn parse_line(line: &str) -> Option<(Board, f64)> {
+    let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+
+    let board = Board::from_str(&fields.join(" ")).ok()?;
+
+    let label = match (line.split_once('['), line.split_once('"')) {
+        (Some((_, rest)), _) => rest.split(']').next()?,
+        (None, Some((_, rest))) => rest.split('"').next()?,
+        (None, None) => line.split_whitespace().last()?,
+    };
+
+    match label.trim() {
+        "1-0" => Some((board, 1.0)),
+        "0-1" => Some((board, 0.0)),
+        "1/2-1/2" => Some((board, 0.5)),
+        label => label
+            .parse()
+            .ok()
+            .filter(|result| (0.0..=1.0).contains(result))
+            .map(|result| (board, result)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_error_is_the_mean_squared_distance_from_the_labels() {
+        // without a tempo bare kings evaluate to exactly 0, which the sigmoid turns into 0.5
+        let params = EvalParams {
+            tempo: 0,
+            ..EvalParams::default()
+        };
+
+        let epd = "4k3/8/8/8/8/8/8/4K3 w - - 0 1 [0.5]\n\
+                   4k3/8/8/8/8/8/8/4K3 b - - c9 \"1-0\";\n\
+                   not a position\n";
+
+        // (0.5 - 0.5)^2 and (1 - 0.5)^2 over two positions
+        assert_eq!(mean_squared_error(epd, &params), (0.125, 2, 1));
+
+        // with one, white to move is ahead by just the tempo
+        let params = EvalParams::default();
+        let expected = sigmoid(params.tempo as f64);
+
+        let epd = "4k3/8/8/8/8/8/8/4K3 w - - 0 1 [1.0]\n4k3/8/8/8/8/8/8/4K3 w - - 0 1 [0.0]\n";
+
+        let (error, ..) = mean_squared_error(epd, &params);
+
+        assert!(expected > 0.5);
+        assert!((error - ((1.0 - expected).powi(2) + expected.powi(2)) / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn labels_parse_in_every_supported_style() {
+        for (line, result) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 [1.0]", Some(1.0)),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 \"0-1\"", Some(0.0)),
+            ("4k3/8/8/8/8/8/8/4K3 w - - c9 \"1/2-1/2\";", Some(0.5)),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 0.25", Some(0.25)),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 [2.0]", None),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 won", None),
+        ] {
+            assert_eq!(parse_line(line).map(|(_, result)| result), result, "{line}");
+        }
+    }
+}
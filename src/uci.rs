@@ -1,22 +1,67 @@
-use crate::{evaluate::Eval, search::INFINITY, EngineReport};
+use crate::{
+    book::NO_BOOK_FILE,
+    evaluate::{Eval, EvalBreakdown, FULL_SCALE},
+    rng::{DEFAULT_SEED, MAX_SEED},
+    search::{
+        SearchLimits, DEFAULT_MOVE_OVERHEAD, INFINITY, MAX_ASPIRATION_DELTA, MAX_ASPIRATION_GROWTH,
+        MAX_CONTEMPT, MAX_ELO, MAX_MOVE_OVERHEAD, MAX_NODES_TIME, MIN_ELO, MIN_MOVE_OVERHEAD,
+    },
+    tt::{DEFAULT_HASH_SIZE, MAX_HASH_SIZE, MIN_HASH_SIZE},
+    EngineReport,
+};
 use chess::ChessMove;
 use chrono::Duration;
 use crossbeam_channel::Sender;
-use std::thread::JoinHandle;
-use vampirc_uci::{UciInfoAttribute, UciMessage, UciTimeControl};
+use std::{io::BufRead, str::FromStr, thread::JoinHandle};
+use vampirc_uci::{UciInfoAttribute, UciMessage, UciOptionConfig, UciTimeControl};
+
+const MAX_THREADS: usize = 64;
+
+const MAX_MULTIPV: u16 = 256;
+
+pub const DEFAULT_PV_LENGTH: usize = 20;
+const MAX_PV_LENGTH: usize = 256;
+
+pub const NO_EVAL_FILE: &str = "<empty>";
+
+pub const STARTPOS_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// win and loss are each a logistic in the score, whatever probability is left over is a draw.
+// the midpoint is where a side is as likely to win as not, the scale is how quickly that changes
+const WDL_MIDPOINT: f64 = 200.0;
+const WDL_SCALE: f64 = 80.0;
+
+const PIECE_NAMES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
 
 pub enum EngineToUci {
     Identify,
     Ready,
     Quit,
-    BestMove(ChessMove),
+    BestMove(Option<ChessMove>),
+    InfoString(String),
+    Perft(Vec<(ChessMove, u64)>),
+    Eval(EvalBreakdown),
+    CurrMove {
+        mv: ChessMove,
+        number: u16,
+    },
+    Progress {
+        depth: u8,
+        time: Duration,
+        nodes: u64,
+        nps: u64,
+        hashfull: u16,
+    },
     Summary {
         depth: u8,
         seldepth: u8,
+        multipv: u16,
         time: Duration,
         cp: Eval,
+        wdl: Option<(u16, u16, u16)>,
         nodes: u64,
         nps: u64,
+        hashfull: u16,
         pv: Vec<chess::ChessMove>,
     },
 }
@@ -27,15 +72,20 @@ pub enum UciToEngine {
     IsReady,
     Register,
     Position(String, Vec<ChessMove>),
-    SetOption,
+    SetOption(EngineOption),
     UciNewGame,
     Stop,
     PonderHit,
     Quit,
-    GoInfinite,
-    GoMoveTime(Duration),
-    GoGameTime(GameTime),
-    Unknown,
+    Go {
+        limits: SearchLimits,
+        search_moves: Vec<ChessMove>,
+    },
+    Perft(u8),
+    Eval,
+    Display,
+    Fen,
+    Unknown(String),
 }
 
 pub struct Uci {
@@ -53,6 +103,20 @@ impl Uci {
         }
     }
 
+    // stands in for the output thread, so tests can read what would have been printed
+    #[cfg(test)]
+    pub fn capture() -> (Uci, crossbeam_channel::Receiver<EngineToUci>) {
+        let (control_tx, control_rx) = crossbeam_channel::unbounded();
+
+        let uci = Uci {
+            control_handle: None,
+            report_handle: None,
+            control_tx: Some(control_tx),
+        };
+
+        (uci, control_rx)
+    }
+
     pub fn init(&mut self, report_tx: Sender<EngineReport>) {
         self.report_thread(report_tx);
         self.control_thread();
@@ -60,107 +124,22 @@ impl Uci {
 
     pub fn send(&mut self, msg: EngineToUci) {
         if let Some(tx) = &self.control_tx {
-            tx.send(msg).unwrap();
+            let _ = tx.send(msg);
         }
     }
 
-    fn report_thread(&mut self, report_tx: Sender<EngineReport>) {
-        let mut incoming_data = String::new();
+    pub fn quit(&mut self) {
+        self.send(EngineToUci::Quit);
 
-        let report_handle = std::thread::spawn(move || {
-            let mut quit = false;
-
-            while !quit {
-                std::io::stdin().read_line(&mut incoming_data).unwrap();
-
-                let msgs = vampirc_uci::parse(&incoming_data);
-
-                for msg in msgs {
-                    let report = match msg {
-                        UciMessage::Uci => UciToEngine::Uci,
-
-                        UciMessage::Debug(debug) => UciToEngine::Debug(debug),
-
-                        UciMessage::IsReady => UciToEngine::IsReady,
-
-                        UciMessage::Register {
-                            later: _,
-                            name: _,
-                            code: _,
-                        } => UciToEngine::Register,
-
-                        UciMessage::Position {
-                            startpos,
-                            fen,
-                            moves,
-                        } => {
-                            let fen = if startpos {
-                                String::from(
-                                    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
-                                )
-                            } else {
-                                fen.unwrap().to_string()
-                            };
-
-                            UciToEngine::Position(fen, moves)
-                        }
-
-                        UciMessage::SetOption { name: _, value: _ } => UciToEngine::SetOption,
-
-                        UciMessage::UciNewGame => UciToEngine::UciNewGame,
-
-                        UciMessage::Stop => UciToEngine::Stop,
-
-                        UciMessage::PonderHit => UciToEngine::PonderHit,
-
-                        UciMessage::Quit => {
-                            quit = true;
-
-                            UciToEngine::Quit
-                        }
-
-                        UciMessage::Go {
-                            time_control,
-                            search_control,
-                        } => {
-                            if let Some(time_control) = time_control {
-                                match time_control {
-                                    UciTimeControl::Ponder => panic!("ponder not implemented"),
-                                    UciTimeControl::Infinite => UciToEngine::GoInfinite,
-                                    UciTimeControl::TimeLeft {
-                                        white_time,
-                                        black_time,
-                                        white_increment,
-                                        black_increment,
-                                        moves_to_go,
-                                    } => UciToEngine::GoGameTime(GameTime {
-                                        white_time: white_time.unwrap_or(Duration::zero()),
-                                        black_time: black_time.unwrap_or(Duration::zero()),
-                                        white_increment: white_increment
-                                            .unwrap_or(Duration::zero()),
-                                        black_increment: black_increment
-                                            .unwrap_or(Duration::zero()),
-                                        moves_to_go,
-                                    }),
-                                    UciTimeControl::MoveTime(movetime) => {
-                                        UciToEngine::GoMoveTime(movetime)
-                                    }
-                                }
-                            } else if let Some(search_control) = search_control {
-                                todo!("{:?}", search_control)
-                            } else {
-                                unreachable!()
-                            }
-                        }
-
-                        _ => UciToEngine::Unknown,
-                    };
-
-                    report_tx.send(EngineReport::Uci(report)).unwrap();
-                }
+        // let everything already queued reach stdout before the process exits
+        if let Some(handle) = self.control_handle.take() {
+            let _ = handle.join();
+        }
+    }
 
-                incoming_data.clear();
-            }
+    fn report_thread(&mut self, report_tx: Sender<EngineReport>) {
+        let report_handle = std::thread::spawn(move || {
+            read_commands(std::io::stdin().lock(), &report_tx);
         });
 
         self.report_handle = Some(report_handle);
@@ -173,26 +152,220 @@ impl Uci {
             let mut quit = false;
 
             while !quit {
-                let msg = control_rx.recv().unwrap();
+                let msg = control_rx.recv().unwrap_or(EngineToUci::Quit);
 
                 match msg {
                     EngineToUci::Identify => {
                         println!("{}", UciMessage::id_name("kittycat"));
                         println!("{}", UciMessage::id_author("skycloudd"));
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("Hash"),
+                                default: Some(DEFAULT_HASH_SIZE as i64),
+                                min: Some(MIN_HASH_SIZE as i64),
+                                max: Some(MAX_HASH_SIZE as i64),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Button {
+                                name: String::from("Clear Hash"),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Check {
+                                name: String::from("Ponder"),
+                                default: Some(false),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("Threads"),
+                                default: Some(1),
+                                min: Some(1),
+                                max: Some(MAX_THREADS as i64),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("MultiPV"),
+                                default: Some(1),
+                                min: Some(1),
+                                max: Some(MAX_MULTIPV as i64),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("PVLength"),
+                                default: Some(DEFAULT_PV_LENGTH as i64),
+                                min: Some(0),
+                                max: Some(MAX_PV_LENGTH as i64),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("Move Overhead"),
+                                default: Some(DEFAULT_MOVE_OVERHEAD as i64),
+                                min: Some(MIN_MOVE_OVERHEAD as i64),
+                                max: Some(MAX_MOVE_OVERHEAD as i64),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("NodesTime"),
+                                default: Some(0),
+                                min: Some(0),
+                                max: Some(MAX_NODES_TIME as i64),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("Contempt"),
+                                default: Some(0),
+                                min: Some(-MAX_CONTEMPT as i64),
+                                max: Some(MAX_CONTEMPT as i64),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::String {
+                                name: String::from("EvalFile"),
+                                default: Some(String::from(NO_EVAL_FILE)),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Check {
+                                name: String::from("UCI_Chess960"),
+                                default: Some(false),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::String {
+                                name: String::from("BookFile"),
+                                default: Some(String::from(NO_BOOK_FILE)),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Check {
+                                name: String::from("BookBestMove"),
+                                default: Some(false),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("Seed"),
+                                default: Some(DEFAULT_SEED as i64),
+                                min: Some(0),
+                                max: Some(MAX_SEED as i64),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Check {
+                                name: String::from("UCI_ShowWDL"),
+                                default: Some(false),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Check {
+                                name: String::from("UCI_LimitStrength"),
+                                default: Some(false),
+                            })
+                        );
+                        println!(
+                            "{}",
+                            UciMessage::Option(UciOptionConfig::Spin {
+                                name: String::from("UCI_Elo"),
+                                default: Some(MAX_ELO as i64),
+                                min: Some(MIN_ELO as i64),
+                                max: Some(MAX_ELO as i64),
+                            })
+                        );
                         println!("{}", UciMessage::UciOk);
                     }
                     EngineToUci::Ready => println!("{}", UciMessage::ReadyOk),
                     EngineToUci::Quit => quit = true,
-                    EngineToUci::BestMove(bestmove) => {
+                    EngineToUci::BestMove(Some(bestmove)) => {
                         println!("{}", UciMessage::best_move(bestmove));
                     }
+                    EngineToUci::BestMove(None) => println!("bestmove (none)"),
+                    EngineToUci::InfoString(message) => {
+                        println!("{}", UciMessage::info_string(message));
+                    }
+                    EngineToUci::Perft(divide) => {
+                        for (legal, nodes) in &divide {
+                            println!("{}: {}", legal, nodes);
+                        }
+
+                        let total: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+
+                        println!();
+                        println!("Nodes searched: {}", total);
+                    }
+                    EngineToUci::Eval(breakdown) => {
+                        println!("material: {}", breakdown.material);
+
+                        for (name, score) in PIECE_NAMES.iter().zip(breakdown.piece_square) {
+                            println!("{} piece square: {}", name, score);
+                        }
+
+                        println!("mobility: {}", breakdown.mobility);
+                        println!("passed pawns: {}", breakdown.passed_pawns);
+                        println!("pawn structure: {}", breakdown.pawn_structure);
+                        println!("bishop pair: {}", breakdown.bishop_pair);
+                        println!("king safety: {}", breakdown.king_safety);
+                        println!("rooks: {}", breakdown.rooks);
+                        println!("tempo (side to move): {}", breakdown.tempo);
+                        println!("scale: {}/{}", breakdown.scale, FULL_SCALE);
+                        println!("phase: {}", breakdown.phase);
+                        println!("score (side to move): {}", breakdown.score);
+                    }
+                    EngineToUci::CurrMove { mv, number } => println!(
+                        "{}",
+                        UciMessage::Info(vec![
+                            UciInfoAttribute::CurrMove(mv),
+                            UciInfoAttribute::CurrMoveNum(number)
+                        ])
+                    ),
+                    EngineToUci::Progress {
+                        depth,
+                        time,
+                        nodes,
+                        nps,
+                        hashfull,
+                    } => println!(
+                        "{}",
+                        UciMessage::Info(vec![
+                            UciInfoAttribute::Depth(depth),
+                            UciInfoAttribute::Time(time),
+                            UciInfoAttribute::Nodes(nodes),
+                            UciInfoAttribute::Nps(nps),
+                            UciInfoAttribute::HashFull(hashfull)
+                        ])
+                    ),
                     EngineToUci::Summary {
                         depth,
                         seldepth,
+                        multipv,
                         time,
                         cp,
+                        wdl,
                         nodes,
                         nps,
+                        hashfull,
                         pv,
                     } => {
                         let (cp, mate) = if cp.abs() > INFINITY / 2 {
@@ -206,23 +379,35 @@ impl Uci {
                             (Some(cp), None)
                         };
 
-                        println!(
-                            "{}",
-                            UciMessage::Info(vec![
-                                UciInfoAttribute::Depth(depth),
-                                UciInfoAttribute::SelDepth(seldepth),
-                                UciInfoAttribute::Time(time),
-                                UciInfoAttribute::Score {
-                                    cp: cp.map(|cp| cp as i32),
-                                    mate,
-                                    lower_bound: None,
-                                    upper_bound: None
-                                },
-                                UciInfoAttribute::Nodes(nodes),
-                                UciInfoAttribute::Nps(nps),
-                                UciInfoAttribute::Pv(pv)
-                            ])
-                        )
+                        let mut info = vec![
+                            UciInfoAttribute::Depth(depth),
+                            UciInfoAttribute::SelDepth(seldepth),
+                            UciInfoAttribute::MultiPv(multipv),
+                            UciInfoAttribute::Time(time),
+                            UciInfoAttribute::Score {
+                                cp: cp.map(|cp| cp as i32),
+                                mate,
+                                lower_bound: None,
+                                upper_bound: None,
+                            },
+                        ];
+
+                        // vampirc_uci has no wdl attribute, so it goes out as a raw name and value
+                        if let Some((win, draw, loss)) = wdl {
+                            info.push(UciInfoAttribute::Any(
+                                String::from("wdl"),
+                                format!("{} {} {}", win, draw, loss),
+                            ));
+                        }
+
+                        info.extend([
+                            UciInfoAttribute::Nodes(nodes),
+                            UciInfoAttribute::Nps(nps),
+                            UciInfoAttribute::HashFull(hashfull),
+                            UciInfoAttribute::Pv(pv),
+                        ]);
+
+                        println!("{}", UciMessage::Info(info))
                     }
                 }
             }
@@ -241,3 +426,566 @@ pub struct GameTime {
     pub black_increment: Duration,
     pub moves_to_go: Option<u8>,
 }
+
+#[derive(Debug, PartialEq)]
+pub enum EngineOption {
+    Hash(usize),
+    ClearHash,
+    Ponder(bool),
+    Threads(usize),
+    MultiPv(u16),
+    PvLength(usize),
+    MoveOverhead(u64),
+    NodesTime(u64),
+    Contempt(Eval),
+    AspirationDelta(Eval),
+    AspirationGrowth(u16),
+    EvalFile(Option<String>),
+    Chess960(bool),
+    BookFile(Option<String>),
+    BookBestMove(bool),
+    Seed(u64),
+    ShowWdl(bool),
+    LimitStrength(bool),
+    Elo(u16),
+}
+
+// permille win, draw and loss for the side to move, a forced mate is certain either way
+pub fn wdl(cp: Eval) -> (u16, u16, u16) {
+    if cp.abs() > INFINITY / 2 {
+        return if cp > 0 { (1000, 0, 0) } else { (0, 0, 1000) };
+    }
+
+    let logistic = |x: f64| 1000.0 / (1.0 + ((WDL_MIDPOINT - x) / WDL_SCALE).exp());
+
+    let win = logistic(cp as f64).round() as u16;
+    let loss = logistic(-cp as f64).round() as u16;
+
+    (win, 1000 - win - loss, loss)
+}
+
+fn read_commands(input: impl BufRead, report_tx: &Sender<EngineReport>) {
+    let mut lines = input.lines();
+
+    let mut quit = false;
+
+    while !quit {
+        // eof means the gui has gone away, which is as good as a quit
+        let Some(Ok(line)) = lines.next() else {
+            let _ = report_tx.send(EngineReport::Uci(UciToEngine::Quit));
+
+            break;
+        };
+
+        for report in parse_line(&line) {
+            // anything after a quit on the same line is dropped with the rest of the input
+            quit = matches!(report, UciToEngine::Quit);
+
+            if report_tx.send(EngineReport::Uci(report)).is_err() {
+                quit = true;
+            }
+
+            if quit {
+                break;
+            }
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Vec<UciToEngine> {
+    vampirc_uci::parse_with_unknown(line)
+        .into_iter()
+        .map(|msg| match msg {
+            UciMessage::Uci => UciToEngine::Uci,
+
+            UciMessage::Debug(debug) => UciToEngine::Debug(debug),
+
+            UciMessage::IsReady => UciToEngine::IsReady,
+
+            UciMessage::Register {
+                later: _,
+                name: _,
+                code: _,
+            } => UciToEngine::Register,
+
+            UciMessage::Position {
+                startpos,
+                fen,
+                moves,
+            } => {
+                // startpos wins if the parser ever hands over both
+                let fen = if startpos {
+                    Some(String::from(STARTPOS_FEN))
+                } else {
+                    fen.map(|fen| fen.to_string())
+                };
+
+                match fen {
+                    Some(fen) => UciToEngine::Position(fen, moves),
+                    None => UciToEngine::Unknown(line.to_string()),
+                }
+            }
+
+            UciMessage::SetOption { name, value } => match parse_option(&name, value.as_deref()) {
+                Some(option) => UciToEngine::SetOption(option),
+                None => UciToEngine::Unknown(line.to_string()),
+            },
+
+            UciMessage::UciNewGame => UciToEngine::UciNewGame,
+
+            UciMessage::Stop => UciToEngine::Stop,
+
+            UciMessage::PonderHit => UciToEngine::PonderHit,
+
+            UciMessage::Quit => UciToEngine::Quit,
+
+            UciMessage::Go {
+                time_control,
+                search_control,
+            } => {
+                let search_moves = search_control
+                    .as_ref()
+                    .map(|search_control| search_control.search_moves.clone())
+                    .unwrap_or_default();
+
+                let mut limits = SearchLimits::default();
+
+                match time_control {
+                    Some(UciTimeControl::Ponder) => limits.ponder = true,
+                    Some(UciTimeControl::Infinite) => limits.infinite = true,
+                    Some(UciTimeControl::TimeLeft {
+                        white_time,
+                        black_time,
+                        white_increment,
+                        black_increment,
+                        moves_to_go,
+                    }) => {
+                        limits.game_time = Some(GameTime {
+                            white_time: white_time.unwrap_or(Duration::zero()),
+                            black_time: black_time.unwrap_or(Duration::zero()),
+                            white_increment: white_increment.unwrap_or(Duration::zero()),
+                            black_increment: black_increment.unwrap_or(Duration::zero()),
+                            moves_to_go,
+                        })
+                    }
+                    Some(UciTimeControl::MoveTime(movetime)) => limits.movetime = Some(movetime),
+                    None => {}
+                }
+
+                if let Some(search_control) = &search_control {
+                    limits.max_depth = search_control.depth;
+                    limits.max_nodes = search_control.nodes;
+                    limits.mate = search_control.mate;
+                }
+
+                // vampirc_uci drops the ponder flag when a time control is also given
+                let is_ponder = line.split_whitespace().any(|t| t == "ponder");
+
+                limits.ponder |= is_ponder;
+
+                // it also keeps only one time control, so a movetime next to the clock is lost
+                if limits.movetime.is_none() {
+                    limits.movetime = line
+                        .split_whitespace()
+                        .skip_while(|&token| token != "movetime")
+                        .nth(1)
+                        .and_then(|ms| ms.parse().ok())
+                        .map(Duration::milliseconds);
+                }
+
+                UciToEngine::Go {
+                    limits,
+                    search_moves,
+                }
+            }
+
+            UciMessage::Unknown(text, _) => {
+                parse_custom(&text).unwrap_or(UciToEngine::Unknown(line.to_string()))
+            }
+
+            _ => UciToEngine::Unknown(line.to_string()),
+        })
+        .collect()
+}
+
+fn parse_custom(text: &str) -> Option<UciToEngine> {
+    let mut tokens = text.split_whitespace();
+
+    match tokens.next()? {
+        "eval" => Some(UciToEngine::Eval),
+        "d" => Some(UciToEngine::Display),
+        "fen" => Some(UciToEngine::Fen),
+        "position" => parse_position(tokens),
+        "perft" => tokens
+            .next()
+            .and_then(|depth| depth.parse().ok())
+            .map(UciToEngine::Perft),
+        _ => None,
+    }
+}
+
+fn parse_position<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Option<UciToEngine> {
+    let fen = match tokens.next()? {
+        // a fen after startpos is contradictory, so only moves may follow
+        "startpos" => match tokens.next() {
+            None | Some("moves") => String::from(STARTPOS_FEN),
+            Some(_) => return None,
+        },
+        "fen" => {
+            let fields: Vec<&str> = tokens
+                .by_ref()
+                .take_while(|&token| token != "moves")
+                .collect();
+
+            fields.join(" ")
+        }
+        _ => return None,
+    };
+
+    let moves = tokens
+        .map(ChessMove::from_str)
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    Some(UciToEngine::Position(fen, moves))
+}
+
+fn parse_option(name: &str, value: Option<&str>) -> Option<EngineOption> {
+    match name.to_lowercase().as_str() {
+        "hash" => value?
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .map(|size| EngineOption::Hash(size.clamp(MIN_HASH_SIZE, MAX_HASH_SIZE))),
+        "clear hash" => Some(EngineOption::ClearHash),
+        "ponder" => value?.trim().parse::<bool>().ok().map(EngineOption::Ponder),
+        "threads" => value?
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .map(|count| EngineOption::Threads(count.clamp(1, MAX_THREADS))),
+        "multipv" => value?
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .map(|lines| EngineOption::MultiPv(lines.clamp(1, MAX_MULTIPV))),
+        "pvlength" => value?
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .map(|length| EngineOption::PvLength(length.min(MAX_PV_LENGTH))),
+        "move overhead" => value?.trim().parse::<u64>().ok().map(|overhead| {
+            EngineOption::MoveOverhead(overhead.clamp(MIN_MOVE_OVERHEAD, MAX_MOVE_OVERHEAD))
+        }),
+        "nodestime" => value?
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .map(|nodes| EngineOption::NodesTime(nodes.min(MAX_NODES_TIME))),
+        "contempt" => value?
+            .trim()
+            .parse::<Eval>()
+            .ok()
+            .map(|contempt| EngineOption::Contempt(contempt.clamp(-MAX_CONTEMPT, MAX_CONTEMPT))),
+        // tuning knobs, accepted but not advertised
+        "aspirationdelta" => value?
+            .trim()
+            .parse::<Eval>()
+            .ok()
+            .map(|delta| EngineOption::AspirationDelta(delta.clamp(1, MAX_ASPIRATION_DELTA))),
+        "aspirationgrowth" => value?.trim().parse::<u16>().ok().map(|growth| {
+            // growth is a percentage, anything between off and 100 would shrink the window
+            let growth = match growth {
+                0 => 0,
+                growth => growth.clamp(100, MAX_ASPIRATION_GROWTH),
+            };
+
+            EngineOption::AspirationGrowth(growth)
+        }),
+        "evalfile" => match value.map(str::trim) {
+            None | Some("") | Some(NO_EVAL_FILE) => Some(EngineOption::EvalFile(None)),
+            Some(path) => Some(EngineOption::EvalFile(Some(path.to_string()))),
+        },
+        "uci_chess960" => value?
+            .trim()
+            .parse::<bool>()
+            .ok()
+            .map(EngineOption::Chess960),
+        "bookfile" => match value.map(str::trim) {
+            None | Some("") | Some(NO_BOOK_FILE) => Some(EngineOption::BookFile(None)),
+            Some(path) => Some(EngineOption::BookFile(Some(path.to_string()))),
+        },
+        "bookbestmove" => value?
+            .trim()
+            .parse::<bool>()
+            .ok()
+            .map(EngineOption::BookBestMove),
+        "seed" => value?
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .map(|seed| EngineOption::Seed(seed.min(MAX_SEED))),
+        "uci_showwdl" => value?
+            .trim()
+            .parse::<bool>()
+            .ok()
+            .map(EngineOption::ShowWdl),
+        "uci_limitstrength" => value?
+            .trim()
+            .parse::<bool>()
+            .ok()
+            .map(EngineOption::LimitStrength),
+        "uci_elo" => value?
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .map(|elo| EngineOption::Elo(elo.clamp(MIN_ELO, MAX_ELO))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // through the same parser the input thread uses, so names with spaces in them are covered too
+    fn setoption(line: &str) -> Option<EngineOption> {
+        match vampirc_uci::parse_with_unknown(line).pop()? {
+            UciMessage::SetOption { name, value } => parse_option(&name, value.as_deref()),
+            message => panic!("{line} parsed as {message:?}"),
+        }
+    }
+
+    fn go(line: &str) -> (SearchLimits, Vec<ChessMove>) {
+        match parse_line(line).pop() {
+            Some(UciToEngine::Go {
+                limits,
+                search_moves,
+            }) => (limits, search_moves),
+            _ => panic!("{line} is not a go"),
+        }
+    }
+
+    fn read(input: &str) -> Vec<UciToEngine> {
+        let (report_tx, report_rx) = crossbeam_channel::unbounded();
+
+        read_commands(input.as_bytes(), &report_tx);
+
+        report_rx
+            .try_iter()
+            .map(|report| match report {
+                EngineReport::Uci(report) => report,
+                EngineReport::Search(_) => panic!("the input thread reported a search"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn buffered_commands_are_read_up_to_the_quit() {
+        let reports = read("isready\nuci\nquit\nisready\n");
+
+        assert!(matches!(
+            reports[..],
+            [UciToEngine::IsReady, UciToEngine::Uci, UciToEngine::Quit]
+        ));
+
+        // a gui closing the pipe counts as a quit
+        assert!(matches!(
+            read("isready\n")[..],
+            [UciToEngine::IsReady, UciToEngine::Quit]
+        ));
+    }
+
+    #[test]
+    fn position_takes_startpos_or_a_fen_with_or_without_moves() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let moves = |list: &[&str]| {
+            list.iter()
+                .map(|m| ChessMove::from_str(m).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        for (line, expected_fen, expected_moves) in [
+            ("position startpos", STARTPOS_FEN, Vec::new()),
+            (
+                "position startpos moves e2e4 e7e5",
+                STARTPOS_FEN,
+                moves(&["e2e4", "e7e5"]),
+            ),
+            (&format!("position fen {kiwipete}"), kiwipete, Vec::new()),
+            (
+                &format!("position fen {kiwipete} moves e1g1 e8c8"),
+                kiwipete,
+                moves(&["e1g1", "e8c8"]),
+            ),
+        ] {
+            match parse_line(line).pop() {
+                Some(UciToEngine::Position(fen, moves)) => {
+                    assert_eq!(fen, expected_fen, "{line}");
+                    assert_eq!(moves, expected_moves, "{line}");
+                }
+                _ => panic!("{line} is not a position"),
+            }
+        }
+    }
+
+    #[test]
+    fn go_nodes_sets_the_node_budget() {
+        let (limits, _) = go("go nodes 5000");
+
+        assert_eq!(limits.max_nodes, Some(5000));
+        assert_eq!(limits.max_depth, None);
+        assert!(!limits.infinite);
+    }
+
+    #[test]
+    fn go_mate_sets_the_mate_distance() {
+        let (limits, _) = go("go mate 3");
+
+        assert_eq!(limits.mate, Some(3));
+        assert_eq!(limits.max_depth, None);
+    }
+
+    #[test]
+    fn searchmoves_are_passed_on() {
+        let (limits, search_moves) = go("go depth 3 searchmoves a2a3 h2h4");
+
+        assert_eq!(limits.max_depth, Some(3));
+        assert_eq!(
+            search_moves,
+            ["a2a3", "h2h4"].map(|m| ChessMove::from_str(m).unwrap())
+        );
+    }
+
+    #[test]
+    fn custom_commands_parse() {
+        assert!(matches!(parse_line("eval")[..], [UciToEngine::Eval]));
+    }
+
+    #[test]
+    fn register_parses_in_any_form() {
+        for line in ["register later", "register name kitty code 1234"] {
+            assert!(
+                matches!(parse_line(line)[..], [UciToEngine::Register]),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn options_parse_and_clamp() {
+        for (line, option) in [
+            ("setoption name Hash value 64", EngineOption::Hash(64)),
+            (
+                "setoption name hash value 0",
+                EngineOption::Hash(MIN_HASH_SIZE),
+            ),
+            (
+                "setoption name HASH value 99999999",
+                EngineOption::Hash(MAX_HASH_SIZE),
+            ),
+            ("setoption name Clear Hash", EngineOption::ClearHash),
+            (
+                "setoption name Ponder value true",
+                EngineOption::Ponder(true),
+            ),
+            ("setoption name Threads value 0", EngineOption::Threads(1)),
+            ("setoption name Threads value 4", EngineOption::Threads(4)),
+            (
+                "setoption name MultiPV value 1000",
+                EngineOption::MultiPv(MAX_MULTIPV),
+            ),
+            ("setoption name PvLength value 0", EngineOption::PvLength(0)),
+            (
+                "setoption name Move Overhead value 100",
+                EngineOption::MoveOverhead(100),
+            ),
+            (
+                "setoption name nodestime value 500",
+                EngineOption::NodesTime(500),
+            ),
+            (
+                "setoption name Contempt value -500",
+                EngineOption::Contempt(-MAX_CONTEMPT),
+            ),
+            (
+                "setoption name AspirationDelta value 0",
+                EngineOption::AspirationDelta(1),
+            ),
+            (
+                "setoption name AspirationGrowth value 0",
+                EngineOption::AspirationGrowth(0),
+            ),
+            (
+                "setoption name AspirationGrowth value 50",
+                EngineOption::AspirationGrowth(100),
+            ),
+            (
+                "setoption name EvalFile value <empty>",
+                EngineOption::EvalFile(None),
+            ),
+            (
+                "setoption name BookFile value book.bin",
+                EngineOption::BookFile(Some(String::from("book.bin"))),
+            ),
+            (
+                "setoption name BookBestMove value true",
+                EngineOption::BookBestMove(true),
+            ),
+            (
+                "setoption name Seed value 99999999999",
+                EngineOption::Seed(MAX_SEED),
+            ),
+            (
+                "setoption name uci_chess960 value true",
+                EngineOption::Chess960(true),
+            ),
+            (
+                "setoption name UCI_ShowWDL value false",
+                EngineOption::ShowWdl(false),
+            ),
+            (
+                "setoption name UCI_LimitStrength value true",
+                EngineOption::LimitStrength(true),
+            ),
+            (
+                "setoption name UCI_Elo value 100",
+                EngineOption::Elo(MIN_ELO),
+            ),
+        ] {
+            assert_eq!(setoption(line), Some(option), "{line}");
+        }
+    }
+
+    #[test]
+    fn bad_and_unknown_options_are_rejected() {
+        for line in [
+            "setoption name Hash",
+            "setoption name Hash value lots",
+            "setoption name Threads value -1",
+            "setoption name BookBestMove value yes",
+            "setoption name Ponder value maybe",
+            "setoption name UCI_Chess960 value maybe",
+        ] {
+            assert_eq!(setoption(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn wdl_is_mostly_a_draw_when_level_and_a_win_when_well_ahead() {
+        let (win, draw, loss) = wdl(0);
+
+        assert_eq!(win, loss);
+        assert!(draw > win + loss, "{draw} permille drawn when level");
+
+        let (win, draw, loss) = wdl(800);
+
+        assert!(win > 990, "{win} permille won a queen up");
+        assert_eq!(win + draw + loss, 1000);
+
+        // the loser's view is the same numbers the other way round
+        assert_eq!(wdl(-800), (loss, draw, win));
+        assert_eq!(wdl(INFINITY - 3), (1000, 0, 0));
+        assert_eq!(wdl(-INFINITY + 3), (0, 0, 1000));
+    }
+}